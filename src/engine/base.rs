@@ -1,27 +1,37 @@
-use std::cell::RefCell;
+use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::fmt;
+use std::iter::FromIterator;
 use std::ops::Mul;
 use std::rc::Rc;
 
 use rand::distributions::{Distribution, Standard};
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
-use super::core::{Piece, Playfield, Rotation, Space, Tetromino};
+use super::core::{spawn_col, CurrentPiece, Piece, Playfield, Rotation, Space, Tetromino};
 
 const AUTO_REPEAT_DELAY: u32 = 12;
 const AUTO_REPEAT_RATE: u32 = 7;
 const LOCK_DELAY: u32 = 30;
 const LINE_CLEAR_DELAY: u32 = 30;
+const PREVIEW_COUNT: usize = 5;
+const DANGER_THRESHOLD: u8 = 4;
+const RECENT_KICK_COUNT: usize = 8;
 
 pub trait Engine {
     fn tick(&mut self) -> State;
-    fn get_playfield(&self) -> Playfield;
+    fn get_playfield(&self) -> &Playfield;
     fn get_current_piece(&self) -> CurrentPiece;
+    /// Returns the current piece and its ghost piece, i.e. where the current piece would land if
+    /// it were hard dropped.
+    fn get_active_pieces(&self) -> (CurrentPiece, CurrentPiece);
     fn get_hold_piece(&self) -> Option<Tetromino>;
     fn get_next_pieces(&self) -> Vec<Tetromino>;
+    /// Returns the number of rows of garbage which have been received but not yet added.
+    fn pending_garbage(&self) -> u8;
 
     fn input_move_left(&self);
     fn input_move_right(&self);
@@ -30,6 +40,11 @@ pub trait Engine {
     fn input_soft_drop(&self);
     fn input_hard_drop(&self);
     fn input_hold(&self);
+    fn input_snap_left(&self);
+    fn input_snap_right(&self);
+    /// Inputs each of the specified actions for the next tick. This is equivalent to calling the
+    /// corresponding `input_*` method for each action.
+    fn submit_inputs(&self, actions: &[Action]);
 }
 
 /// The main game engine.
@@ -39,25 +54,58 @@ pub struct BaseEngine {
     tetromino_generator: Box<dyn TetrominoGenerator>,
     hold_piece: Option<Tetromino>,
     is_hold_available: bool,
-    current_tick_inputs: RefCell<HashSet<Action>>,
+    current_tick_inputs: RefCell<ActionSet>,
     current_inputs: HashMap<Action, u32>,
     gravity: Gravity,
-    next_pieces: VecDeque<Tetromino>,
+    gravity_enabled: bool,
+    gravity_direction: GravityDirection,
+    spawn_policy: SpawnPolicy,
+    big_mode: bool,
+    action_order: ActionOrder,
+    auto_repeat_enabled: bool,
+    soft_drop_rate: u32,
+    das_carry: bool,
+    spawn_push_up: bool,
+    hold_over_hard_drop: bool,
+    max_soft_drop_rows_per_tick: Option<u8>,
+    lock_flash_grace: u32,
+    lock_reset_mode: LockResetMode,
+    clear_gravity: ClearGravity,
+    rotation_buffer_ticks: u32,
+    // A rotation which failed while falling and the number of further ticks it remains buffered.
+    buffered_rotation: Option<(Action, u32)>,
+    entry_delay: u32,
+    // Filled lazily, so that the generator is not called until the queue is first used.
+    next_pieces: RefCell<VecDeque<Tetromino>>,
+    preview_count: usize,
+    danger_threshold: u8,
+    pending_garbage: VecDeque<(u8, GarbagePattern)>,
+    garbage_timing: GarbageTiming,
+    // Whether a piece has locked since the last spawn, i.e. the next spawn is not from a hold.
+    locked_since_spawn: bool,
     state: State,
     current_t_spin: TSpinInternal,
+    last_action_was_rotation: bool,
+    last_hard_drop: Option<HardDropInfo>,
+    holes_created_by_last_lock: u32,
+    effective_inputs: u32,
+    wasted_inputs: u32,
+    recent_kicks: VecDeque<(Rotation, Rotation, (i8, i8))>,
     observers: Vec<Rc<dyn BaseEngineObserver>>,
+    recording: Option<Recording>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum State {
     Spawn,
+    Are(u32),
     Falling(u32),
     Lock(u32),
     LineClear(u32),
     TopOut,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum Gravity {
     TicksPerRow(u8),
     RowsPerTick(u8),
@@ -98,8 +146,133 @@ impl Mul<f64> for Gravity {
     }
 }
 
+/// The direction in which pieces fall.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GravityDirection {
+    /// Pieces spawn above the playfield and fall down.
+    Down,
+    /// Pieces spawn at the bottom of the playfield and rise to the top of the visible playfield.
+    Up,
+}
+
+/// Where pieces spawn when gravity is down.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SpawnPolicy {
+    /// Pieces spawn with their lowest blocks on the top row of the visible playfield.
+    VisibleTop,
+    /// Pieces spawn entirely above the visible playfield.
+    AboveField,
+}
+
+impl GravityDirection {
+    /// The change in row when a piece drops by one row.
+    fn row_offset(self) -> i8 {
+        match self {
+            GravityDirection::Down => -1,
+            GravityDirection::Up => 1,
+        }
+    }
+}
+
+/// The order in which moves and rotations are applied when both occur on the same tick.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ActionOrder {
+    /// Move the piece, then rotate it.
+    MoveFirst,
+    /// Rotate the piece, then move it.
+    RotateFirst,
+}
+
+/// Which moves and rotations reset the lock delay while a piece is on the ground.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LockResetMode {
+    /// Every move or rotation resets the lock delay.
+    Move,
+    /// Moves and rotations only reset the lock delay if they lower the piece.
+    Step,
+    /// Moves and rotations never reset the lock delay.
+    None,
+}
+
+/// How blocks above cleared rows fall after a line clear.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClearGravity {
+    /// Rows above the cleared rows drop down by the number of rows cleared below them, leaving
+    /// floating blocks in place.
+    Naive,
+    /// After rows are cleared, each block falls down its column until it lands on the floor or
+    /// another block. Rows filled by falling blocks are then cleared as a separate line clear, and
+    /// this repeats until no rows are full.
+    Cascade,
+}
+
+/// Determines the hole column of each row of garbage.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GarbagePattern {
+    /// Every row has a hole in the same column.
+    Clean(u8),
+    /// Each row has a hole in a different column than the row below it, chosen randomly using the
+    /// specified seed.
+    Cheese(u64),
+}
+
+/// When received garbage is added to the playfield.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GarbageTiming {
+    /// Garbage is added as soon as it is received.
+    Immediate,
+    /// Garbage is added when a piece locks without clearing any rows. Garbage received while
+    /// clearing rows waits for a later piece.
+    OnLock,
+    /// Garbage is added just before the next piece spawns after a piece locks. Pieces swapped in
+    /// by holding do not add garbage.
+    OnSpawn,
+}
+
+impl GarbagePattern {
+    /// Returns the hole column for each of the specified number of rows, from bottom to top.
+    pub fn hole_cols(self, rows: u8) -> Vec<u8> {
+        match self {
+            GarbagePattern::Clean(col) => vec![col; rows as usize],
+            GarbagePattern::Cheese(seed) => {
+                let mut rng = seeded_rng(seed);
+                let mut cols: Vec<u8> = Vec::with_capacity(rows as usize);
+                for _ in 0..rows {
+                    let col = match cols.last() {
+                        // Skip over the previous column so that consecutive holes always differ.
+                        Option::Some(&previous) => {
+                            let col = rng.gen_range(1, Playfield::WIDTH);
+                            if col >= previous {
+                                col + 1
+                            }
+                            else {
+                                col
+                            }
+                        }
+                        Option::None => rng.gen_range(1, Playfield::WIDTH + 1),
+                    };
+                    cols.push(col);
+                }
+                cols
+            }
+        }
+    }
+}
+
+/// The rows and columns covered by a hard drop, e.g. for drawing a hard drop trail.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct HardDropInfo {
+    /// The lowest row occupied by the piece before it was dropped.
+    pub start_row: i8,
+    /// The lowest row occupied by the piece after it was dropped.
+    pub end_row: i8,
+    /// The leftmost and rightmost columns occupied by the piece.
+    pub cols: (i8, i8),
+}
+
+/// An action which can be performed on the current piece.
 #[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
-enum Action {
+pub enum Action {
     MoveLeft,
     MoveRight,
     RotateClockwise,
@@ -107,9 +280,13 @@ enum Action {
     SoftDrop,
     HardDrop,
     Hold,
+    /// Move the piece as far left as possible.
+    SnapLeft,
+    /// Move the piece as far right as possible.
+    SnapRight,
 }
 
-const ALL_ACTIONS: [Action; 7] = [
+const ALL_ACTIONS: [Action; 9] = [
     Action::MoveLeft,
     Action::MoveRight,
     Action::RotateClockwise,
@@ -117,49 +294,93 @@ const ALL_ACTIONS: [Action; 7] = [
     Action::SoftDrop,
     Action::HardDrop,
     Action::Hold,
+    Action::SnapLeft,
+    Action::SnapRight,
 ];
 
-/// The current piece on the playfield.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub struct CurrentPiece {
-    piece: Piece,
-    // Position of lower-left corner of bounding box.
-    row: i8,
-    col: i8,
-}
+/// A set of actions, stored as one bit for each action so that it can be copied without
+/// allocating.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+struct ActionSet(u16);
 
-impl CurrentPiece {
-    /// Creates a new piece in spawn position.
-    fn new(shape: Tetromino) -> CurrentPiece {
-        CurrentPiece {
-            piece: Piece::new(shape),
-            row: 19,
-            col: 4,
-        }
+impl ActionSet {
+    fn bit(action: Action) -> u16 {
+        1 << action as u16
+    }
+
+    fn contains(&self, action: &Action) -> bool {
+        self.0 & ActionSet::bit(*action) != 0
+    }
+
+    /// Adds the action to the set. Returns whether the action was not already in the set.
+    fn insert(&mut self, action: Action) -> bool {
+        let inserted = !self.contains(&action);
+        self.0 |= ActionSet::bit(action);
+        inserted
+    }
+
+    /// Removes the action from the set. Returns whether the action was in the set.
+    fn remove(&mut self, action: &Action) -> bool {
+        let removed = self.contains(action);
+        self.0 &= !ActionSet::bit(*action);
+        removed
     }
 
-    fn rotate_cw(&mut self) {
-        self.piece.rotate_cw();
+    fn is_empty(&self) -> bool {
+        self.0 == 0
     }
 
-    fn rotate_ccw(&mut self) {
-        self.piece.rotate_ccw();
+    /// Returns an iterator over the actions in the set, in the order of `ALL_ACTIONS`.
+    fn iter(&self) -> impl Iterator<Item = &'static Action> {
+        let set = *self;
+        ALL_ACTIONS
+            .iter()
+            .filter(move |action| set.contains(action))
     }
+}
 
-    pub fn get_bounding_box(self) -> [[Space; 4]; 4] {
-        self.piece.get_bounding_box()
+impl FromIterator<Action> for ActionSet {
+    fn from_iter<I: IntoIterator<Item = Action>>(actions: I) -> ActionSet {
+        let mut set = ActionSet::default();
+        set.extend(actions);
+        set
     }
+}
 
-    pub fn get_row(self) -> i8 {
-        self.row
+impl Extend<Action> for ActionSet {
+    fn extend<I: IntoIterator<Item = Action>>(&mut self, actions: I) {
+        for action in actions {
+            self.insert(action);
+        }
     }
+}
+
+/// A direction in which a piece can be rotated.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RotateDir {
+    Clockwise,
+    CounterClockwise,
+}
 
-    pub fn get_col(self) -> i8 {
-        self.col
+/// Clears any rows of the specified playfield that are full, then drops the remaining blocks
+/// according to the specified clear gravity. With cascade gravity, this repeats until no rows are
+/// full. Returns the number of rows cleared by each repetition.
+fn remove_full_rows(playfield: &mut Playfield, clear_gravity: ClearGravity) -> Vec<u8> {
+    let mut cascades = vec![];
+    loop {
+        let n_rows = playfield.remove_full_rows();
+        if n_rows == 0 {
+            return cascades;
+        }
+        cascades.push(n_rows);
+        if clear_gravity == ClearGravity::Naive {
+            return cascades;
+        }
+        playfield.apply_column_gravity();
     }
 }
 
-#[derive(PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum TSpinInternal {
     None,
     Regular,
@@ -167,7 +388,7 @@ enum TSpinInternal {
     PointFive,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum TSpin {
     None,
     Regular,
@@ -185,42 +406,78 @@ impl From<&TSpinInternal> for TSpin {
 }
 
 pub trait BaseEngineObserver {
-    fn on_lock(&self, t_spin: TSpin) {}
+    /// Called when a piece spawns, including when a piece is swapped in from hold.
+    fn on_spawn(&self, shape: Tetromino) {}
+    /// Called when a piece locks, with the kind of T-spin it was, if any.
+    fn on_lock(&self, t_spin: TSpin, playfield: &Playfield) {}
     fn on_soft_drop(&self, n_rows: u8) {}
     fn on_hard_drop(&self, n_rows: u8) {}
+    /// Called when rows are cleared. The rows were cleared by the piece from the most recent call
+    /// to `on_lock`, so its T-spin applies to this clear. With cascade clear gravity, this may be
+    /// called again for the same lock; the T-spin only applies to the first clear. In big mode or
+    /// with cascade clear gravity, `n_rows` may be greater than 4.
     fn on_line_clear(&self, n_rows: u8) {}
+    fn on_hold(&self, swapped_out: Tetromino, swapped_in: Tetromino) {}
+    fn on_action(&self, action: Action) {}
+    /// Called by engines which track levels when the level increases.
+    fn on_level_up(&self, new_level: u8) {}
+    /// Called at the end of every tick with the resulting playfield and current piece.
+    fn on_tick(&self, playfield: &Playfield, current_piece: CurrentPiece) {}
 }
 
 impl Engine for BaseEngine {
     fn tick(&mut self) -> State {
+        if self.recording.is_some() {
+            let settings = self.settings();
+            let inputs = *self.current_tick_inputs.borrow();
+            if let Option::Some(recording) = &mut self.recording {
+                recording.record_tick(settings, inputs);
+            }
+        }
+
         // Always process input so that hold durations are accurate.
         let actions = self.process_input();
 
         match self.state {
             State::Spawn => self.tick_spawn(),
+            State::Are(_) => self.tick_are(),
             State::Falling(_) => self.tick_falling(&actions),
             State::Lock(_) => self.tick_lock(&actions),
             State::LineClear(_) => self.tick_line_clear(),
             State::TopOut => (),
         }
 
+        self.notify_observers(|obs| obs.on_tick(&self.playfield, self.current_piece));
         self.state
     }
 
-    fn get_playfield(&self) -> Playfield {
-        self.playfield
+    fn get_playfield(&self) -> &Playfield {
+        &self.playfield
     }
 
     fn get_current_piece(&self) -> CurrentPiece {
         self.current_piece
     }
 
+    fn get_active_pieces(&self) -> (CurrentPiece, CurrentPiece) {
+        (self.current_piece, self.ghost_piece())
+    }
+
     fn get_hold_piece(&self) -> Option<Tetromino> {
         self.hold_piece
     }
 
     fn get_next_pieces(&self) -> Vec<Tetromino> {
-        Vec::from(self.next_pieces.clone())
+        self.next_pieces_iter().collect()
+    }
+
+    fn pending_garbage(&self) -> u8 {
+        let rows: u32 = self
+            .pending_garbage
+            .iter()
+            .map(|(rows, _)| u32::from(*rows))
+            .sum();
+        rows.min(u32::from(u8::MAX)) as u8
     }
 
     fn input_move_left(&self) {
@@ -250,15 +507,28 @@ impl Engine for BaseEngine {
     fn input_hold(&self) {
         self.input_action(Action::Hold);
     }
+
+    fn input_snap_left(&self) {
+        self.input_action(Action::SnapLeft);
+    }
+
+    fn input_snap_right(&self) {
+        self.input_action(Action::SnapRight);
+    }
+
+    fn submit_inputs(&self, actions: &[Action]) {
+        self.current_tick_inputs
+            .borrow_mut()
+            .extend(actions.iter().cloned());
+    }
 }
 impl BaseEngine {
     /// Creates a new engine with the specified tetromino generator.
     fn with_tetromino_generator(tetromino_generator: Box<dyn TetrominoGenerator>) -> BaseEngine {
+        let tetromino_generator: Box<dyn TetrominoGenerator> = Box::new(ValidatingGenerator {
+            generator: tetromino_generator,
+        });
         let current_piece = CurrentPiece::new(tetromino_generator.next());
-        let mut next_pieces = VecDeque::with_capacity(5);
-        for _ in 0..5 {
-            next_pieces.push_back(tetromino_generator.next());
-        }
         let mut current_inputs = HashMap::new();
         for action in ALL_ACTIONS.iter() {
             current_inputs.insert(*action, 0u32);
@@ -269,13 +539,42 @@ impl BaseEngine {
             tetromino_generator,
             hold_piece: Option::None,
             is_hold_available: true,
-            current_tick_inputs: RefCell::new(HashSet::new()),
+            current_tick_inputs: RefCell::new(ActionSet::default()),
             current_inputs,
             gravity: Gravity::TicksPerRow(30),
-            next_pieces,
+            gravity_enabled: true,
+            gravity_direction: GravityDirection::Down,
+            spawn_policy: SpawnPolicy::AboveField,
+            big_mode: false,
+            action_order: ActionOrder::MoveFirst,
+            auto_repeat_enabled: true,
+            soft_drop_rate: 1,
+            das_carry: true,
+            spawn_push_up: false,
+            hold_over_hard_drop: true,
+            max_soft_drop_rows_per_tick: Option::None,
+            lock_flash_grace: 0,
+            lock_reset_mode: LockResetMode::Move,
+            clear_gravity: ClearGravity::Naive,
+            rotation_buffer_ticks: 0,
+            buffered_rotation: Option::None,
+            entry_delay: 0,
+            next_pieces: RefCell::new(VecDeque::with_capacity(PREVIEW_COUNT)),
+            preview_count: PREVIEW_COUNT,
+            danger_threshold: DANGER_THRESHOLD,
+            pending_garbage: VecDeque::new(),
+            garbage_timing: GarbageTiming::OnLock,
+            locked_since_spawn: false,
             state: State::Falling(0),
             current_t_spin: TSpinInternal::None,
+            last_action_was_rotation: false,
+            last_hard_drop: Option::None,
+            holes_created_by_last_lock: 0,
+            effective_inputs: 0,
+            wasted_inputs: 0,
+            recent_kicks: VecDeque::with_capacity(RECENT_KICK_COUNT),
             observers: vec![],
+            recording: Option::None,
         }
     }
 
@@ -284,6 +583,35 @@ impl BaseEngine {
         BaseEngine::with_tetromino_generator(Box::new(BagGenerator::new()))
     }
 
+    /// Creates a new engine whose pieces are generated from the specified seed.
+    pub fn with_seed(seed: u64) -> BaseEngine {
+        BaseEngine::with_tetromino_generator(Box::new(SeededBagGenerator::new(seed)))
+    }
+
+    /// Creates a new engine which generates pieces from the specified sequence, repeating it when
+    /// it runs out.
+    pub fn with_sequence(sequence: &[Tetromino]) -> BaseEngine {
+        assert!(!sequence.is_empty(), "sequence must not be empty.");
+        BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(sequence)))
+    }
+
+    /// Creates a new engine with default settings which records every generated piece and every
+    /// tick's input, so that the game can be replayed with `Replay::from_game`.
+    pub fn with_recording() -> BaseEngine {
+        let pieces = Rc::new(RefCell::new(vec![]));
+        let generator = RecordingGenerator {
+            generator: Box::new(BagGenerator::new()),
+            pieces: Rc::clone(&pieces),
+        };
+        let mut engine = BaseEngine::with_tetromino_generator(Box::new(generator));
+        engine.recording = Option::Some(Recording {
+            pieces,
+            inputs: vec![],
+            settings: vec![],
+        });
+        engine
+    }
+
     pub fn add_observer(&mut self, observer: Rc<dyn BaseEngineObserver>) {
         self.observers.push(observer);
     }
@@ -297,8 +625,536 @@ impl BaseEngine {
         }
     }
 
+    /// Sets the gravity. `Gravity::TicksPerRow(0)` is treated as `Gravity::TicksPerRow(1)`, i.e.
+    /// the piece drops one row per tick.
     pub fn set_gravity(&mut self, gravity: Gravity) {
-        self.gravity = gravity;
+        self.gravity = match gravity {
+            Gravity::TicksPerRow(0) => Gravity::TicksPerRow(1),
+            _ => gravity,
+        };
+    }
+
+    /// Returns the number of ticks until gravity drops the current piece by one row. Returns
+    /// `Option::None` if the piece is not falling or if gravity drops multiple rows every tick.
+    pub fn ticks_until_drop(&self) -> Option<u32> {
+        match (self.state, self.gravity) {
+            (State::Falling(n), Gravity::TicksPerRow(tpr)) => {
+                Option::Some(u32::from(tpr).saturating_sub(n))
+            }
+            _ => Option::None,
+        }
+    }
+
+    /// Returns whether the current piece would fit, without colliding, with its bounding box at the
+    /// specified position and the specified rotation.
+    pub fn piece_fits_at(&self, row: i8, col: i8, rotation: Rotation) -> bool {
+        let mut piece = Piece::new(*self.current_piece.piece.get_shape());
+        while piece.get_rotation() != &rotation {
+            piece.rotate_cw();
+        }
+        let scale = self.current_piece.scale;
+        !self.has_collision_with_piece(CurrentPiece {
+            piece,
+            row,
+            col,
+            scale,
+        })
+    }
+
+    /// Adds the specified number of rows of garbage to the bottom of the playfield, each with a
+    /// hole in the specified column. The current piece is pushed up if it would overlap the garbage.
+    pub fn add_garbage(&mut self, rows: u8, hole_col: u8) {
+        self.add_garbage_pattern(rows, GarbagePattern::Clean(hole_col));
+    }
+
+    /// Adds the specified number of rows of garbage to the bottom of the playfield, with holes
+    /// determined by the specified pattern. If the current piece would overlap the garbage, it is
+    /// pushed up along with the rest of the playfield. Since garbage always rises from the bottom,
+    /// this is the case regardless of gravity direction. In big mode, the piece is pushed by a
+    /// multiple of its scale so that it stays aligned to its grid.
+    pub fn add_garbage_pattern(&mut self, rows: u8, pattern: GarbagePattern) {
+        for hole_col in pattern.hole_cols(rows) {
+            self.playfield.add_garbage_row(hole_col);
+        }
+        if rows > 0 && self.has_collision() {
+            let scale = self.current_piece.scale;
+            let rows = rows as i8;
+            self.current_piece.row += (rows + scale - 1) / scale * scale;
+        }
+    }
+
+    /// Receives the specified number of rows of garbage. Unless the garbage timing is immediate,
+    /// the garbage is queued, then added to the bottom of the playfield at the time determined by
+    /// the garbage timing. By default, this is the next time a piece locks without clearing any
+    /// rows.
+    pub fn receive_garbage(&mut self, rows: u8, pattern: GarbagePattern) {
+        if rows == 0 {
+            return;
+        }
+        match self.garbage_timing {
+            GarbageTiming::Immediate => self.add_garbage_pattern(rows, pattern),
+            GarbageTiming::OnLock | GarbageTiming::OnSpawn => {
+                self.pending_garbage.push_back((rows, pattern))
+            }
+        }
+    }
+
+    /// Sets when received garbage is added to the playfield.
+    pub fn set_garbage_timing(&mut self, garbage_timing: GarbageTiming) {
+        self.garbage_timing = garbage_timing;
+    }
+
+    /// Cancels pending garbage with the specified number of rows of outgoing attack, starting with
+    /// the garbage received first. Returns the remaining attack, which should be sent to the
+    /// opponent.
+    pub fn apply_attack(&mut self, outgoing: u8) -> u8 {
+        let mut remaining = outgoing;
+        while remaining > 0 {
+            match self.pending_garbage.front_mut() {
+                Option::Some((rows, _)) if *rows > remaining => {
+                    *rows -= remaining;
+                    remaining = 0;
+                }
+                Option::Some((rows, _)) => {
+                    remaining -= *rows;
+                    self.pending_garbage.pop_front();
+                }
+                Option::None => break,
+            }
+        }
+        remaining
+    }
+
+    /// Moves the current piece left until it reaches a wall or block. Returns the number of columns
+    /// moved.
+    pub fn snap_left(&mut self) -> u8 {
+        self.snap(-(Playfield::WIDTH as i8))
+    }
+
+    /// Moves the current piece right until it reaches a wall or block. Returns the number of
+    /// columns moved.
+    pub fn snap_right(&mut self) -> u8 {
+        self.snap(Playfield::WIDTH as i8)
+    }
+
+    fn snap(&mut self, col_offset: i8) -> u8 {
+        let cols = self.move_piece(col_offset);
+        if cols > 0 {
+            self.current_t_spin = TSpinInternal::None;
+            self.last_action_was_rotation = false;
+        }
+        cols
+    }
+
+    /// Returns whether no tetromino could spawn with the current playfield and spawn settings.
+    pub fn is_topped_out(&self) -> bool {
+        Tetromino::ALL
+            .iter()
+            .all(|shape| self.spawn_position(self.spawn_piece(*shape)).is_none())
+    }
+
+    /// Returns the number of holes created by the most recently locked piece, before any rows were
+    /// cleared. Returns 0 if no piece has locked.
+    pub fn holes_created_by_last_lock(&self) -> u32 {
+        self.holes_created_by_last_lock
+    }
+
+    /// Returns the number of moves and rotations which were input and changed the position of the
+    /// current piece.
+    pub fn get_effective_inputs(&self) -> u32 {
+        self.effective_inputs
+    }
+
+    /// Returns the number of moves and rotations which were input but had no effect, e.g. moving
+    /// into a wall or a rotation which could not be kicked into place.
+    pub fn get_wasted_inputs(&self) -> u32 {
+        self.wasted_inputs
+    }
+
+    /// Returns the most recent successful rotations, from oldest to newest, as (initial rotation,
+    /// new rotation, (col_offset, row_offset)), where the offset is the wall kick which was applied.
+    pub fn get_recent_kicks(&self) -> Vec<(Rotation, Rotation, (i8, i8))> {
+        self.recent_kicks.iter().cloned().collect()
+    }
+
+    /// Fills the bottom row, then immediately clears all full rows and notifies observers, as if
+    /// the rows were cleared by a piece. Returns the number of rows cleared. Intended for debugging.
+    pub fn debug_clear_bottom_row(&mut self) -> u8 {
+        for col in 1..=Playfield::WIDTH {
+            self.playfield.set(1, col);
+        }
+        self.clear_rows()
+    }
+
+    /// Inputs each of the specified actions, then ticks the engine. This is equivalent to calling
+    /// the corresponding `input_*` method for each action before calling `tick`.
+    pub fn tick_with_inputs(&mut self, actions: &HashSet<Action>) -> State {
+        self.tick_with_action_set(actions.iter().cloned().collect())
+    }
+
+    fn tick_with_action_set(&mut self, actions: ActionSet) -> State {
+        self.current_tick_inputs
+            .borrow_mut()
+            .extend(actions.iter().cloned());
+        self.tick()
+    }
+
+    /// Returns the current state of the engine.
+    pub fn get_state(&self) -> State {
+        self.state
+    }
+
+    /// Returns the most recent hard drop, or `Option::None` if the current piece has spawned since.
+    pub fn get_last_hard_drop(&self) -> Option<HardDropInfo> {
+        self.last_hard_drop
+    }
+
+    /// Returns the T-spin which would be awarded if the current piece were rotated in the specified
+    /// direction, applying a wall kick if necessary, and then locked. Returns `TSpin::None` if the
+    /// rotation is not possible. Does not modify the current piece.
+    pub fn would_be_tspin(&self, direction: RotateDir) -> TSpin {
+        let initial = *self.current_piece.piece.get_rotation();
+        let mut rotated_piece = self.current_piece;
+        match direction {
+            RotateDir::Clockwise => rotated_piece.rotate_cw(),
+            RotateDir::CounterClockwise => rotated_piece.rotate_ccw(),
+        }
+        let rotated = *rotated_piece.piece.get_rotation();
+
+        match self.check_rotation(&mut rotated_piece, initial, rotated) {
+            Option::Some((_, rotation_point)) => {
+                let previous = if rotation_point == 5 {
+                    TSpinInternal::PointFive
+                }
+                else {
+                    self.current_t_spin
+                };
+                TSpin::from(&self.detect_t_spin(rotated_piece, previous))
+            }
+            Option::None => TSpin::None,
+        }
+    }
+
+    /// Returns the number of rows the current piece would fall if it were hard dropped.
+    pub fn hard_drop_distance(&self) -> u8 {
+        let row_offset = self.gravity_direction.row_offset() * self.current_piece.scale;
+        ((self.ghost_piece().row - self.current_piece.row) / row_offset) as u8
+    }
+
+    /// Returns the points which would be awarded for hard dropping the current piece.
+    pub fn hard_drop_score_preview(&self) -> u32 {
+        2 * u32::from(self.hard_drop_distance())
+    }
+
+    /// Returns whether the stack has reached the dangerous rows at the top of the visible playfield.
+    pub fn is_in_danger(&self) -> bool {
+        match self.playfield.highest_block_row() {
+            Option::Some(row) => {
+                row.saturating_add(self.danger_threshold) > Playfield::VISIBLE_HEIGHT
+            }
+            Option::None => false,
+        }
+    }
+
+    /// Returns the number of pieces in the next queue before the first piece with the specified
+    /// shape, or `Option::None` if it is not in the visible part of the queue.
+    pub fn pieces_until(&self, shape: Tetromino) -> Option<usize> {
+        self.next_pieces_iter().position(|piece| piece == shape)
+    }
+
+    /// Returns an iterator over the visible pieces in the next queue, without copying the queue.
+    pub fn next_pieces_iter(&self) -> impl Iterator<Item = Tetromino> + '_ {
+        self.fill_next_pieces();
+        NextPiecesIter {
+            next_pieces: self.next_pieces.borrow(),
+            index: 0,
+            count: self.preview_count,
+        }
+    }
+
+    /// Returns each placement which the current piece can reach from its current position by moving,
+    /// rotating, and soft dropping, along with a sequence of actions which reaches it. Every
+    /// placement is resting on a block or the floor. Placements which cover the same spaces are
+    /// only included once.
+    pub fn reachable_placements(&self) -> Vec<(CurrentPiece, Vec<Action>)> {
+        let row_offset = self.gravity_direction.row_offset() * self.current_piece.scale;
+        let col_offset = self.current_piece.scale;
+        let mut paths = HashMap::new();
+        paths.insert(self.current_piece, vec![]);
+        let mut queue = VecDeque::new();
+        queue.push_back(self.current_piece);
+
+        let mut placements = vec![];
+        let mut placed_cells = HashSet::new();
+        while let Option::Some(piece) = queue.pop_front() {
+            let path: Vec<Action> = paths[&piece].clone();
+
+            let shifted = |col_offset: i8, row_offset: i8| {
+                let mut shifted = piece;
+                shifted.col += col_offset;
+                shifted.row += row_offset;
+                if self.has_collision_with_piece(shifted) {
+                    Option::None
+                }
+                else {
+                    Option::Some(shifted)
+                }
+            };
+
+            let dropped = shifted(0, row_offset);
+            if dropped.is_none() {
+                let mut cells = piece.get_cells();
+                cells.sort();
+                if placed_cells.insert(cells) {
+                    placements.push((piece, path.clone()));
+                }
+            }
+
+            let neighbors = [
+                (Action::MoveLeft, shifted(-col_offset, 0)),
+                (Action::MoveRight, shifted(col_offset, 0)),
+                (
+                    Action::RotateClockwise,
+                    self.rotated_piece(piece, CurrentPiece::rotate_cw),
+                ),
+                (
+                    Action::RotateCounterClockwise,
+                    self.rotated_piece(piece, CurrentPiece::rotate_ccw),
+                ),
+                (Action::SoftDrop, dropped),
+            ];
+            for (action, neighbor) in neighbors.iter() {
+                if let Option::Some(neighbor) = neighbor {
+                    if !paths.contains_key(neighbor) {
+                        let mut neighbor_path = path.clone();
+                        neighbor_path.push(*action);
+                        paths.insert(*neighbor, neighbor_path);
+                        queue.push_back(*neighbor);
+                    }
+                }
+            }
+        }
+
+        placements
+    }
+
+    /// Returns the (row, col) position of each block of the current piece which is directly above
+    /// a block or the floor.
+    pub fn resting_cells(&self) -> Vec<(u8, u8)> {
+        self.current_piece
+            .get_cells()
+            .into_iter()
+            .filter(|&(row, col)| {
+                row <= 1 || self.playfield.get(row as u8 - 1, col as u8) == Space::Block
+            })
+            .map(|(row, col)| (row as u8, col as u8))
+            .collect()
+    }
+
+    /// Sets the direction in which pieces fall. The current piece is moved to the spawn position for
+    /// the new direction, so this should be set before the game starts.
+    pub fn set_gravity_direction(&mut self, gravity_direction: GravityDirection) {
+        self.gravity_direction = gravity_direction;
+        self.current_piece = self.spawn_piece(*self.current_piece.piece.get_shape());
+    }
+
+    /// Sets where pieces spawn when gravity is down. The current piece is moved to the new spawn
+    /// position, so this should be set before the game starts.
+    pub fn set_spawn_policy(&mut self, spawn_policy: SpawnPolicy) {
+        self.spawn_policy = spawn_policy;
+        self.current_piece = self.spawn_piece(*self.current_piece.piece.get_shape());
+    }
+
+    /// Sets whether or not big mode is enabled. In big mode, each block of a piece occupies a 2 x 2
+    /// square, and pieces move and drop 2 spaces at a time. The current piece is moved to the new
+    /// spawn position, so this should be set before the game starts.
+    pub fn set_big_mode(&mut self, big_mode: bool) {
+        self.big_mode = big_mode;
+        self.current_piece = self.spawn_piece(*self.current_piece.piece.get_shape());
+    }
+
+    /// Sets the number of next pieces which are visible.
+    pub fn set_preview_count(&mut self, preview_count: usize) {
+        self.preview_count = preview_count;
+    }
+
+    /// Reseeds the tetromino generator, if it is seeded. Pieces already in the next queue are not
+    /// affected. The queue is first filled to at least the default preview count, so the number of
+    /// pieces generated from the old seed does not depend on the preview count. Returns whether or
+    /// not the generator was reseeded.
+    pub fn reseed_generator(&self, seed: u64) -> bool {
+        match self.tetromino_generator.as_reseedable() {
+            Option::Some(generator) => {
+                // Generate the queue from the old seed, whether or not it has been used yet.
+                self.fill_next_pieces_to(self.preview_count.max(PREVIEW_COUNT));
+                generator.reseed(seed);
+                true
+            }
+            Option::None => false,
+        }
+    }
+
+    /// Sets the number of rows at the top of the visible playfield which are considered dangerous.
+    pub fn set_danger_threshold(&mut self, danger_threshold: u8) {
+        self.danger_threshold = danger_threshold;
+    }
+
+    /// Sets the order in which moves and rotations are applied when both occur on the same tick.
+    pub fn set_action_order(&mut self, action_order: ActionOrder) {
+        self.action_order = action_order;
+    }
+
+    /// Sets whether or not holding a move input repeatedly moves the piece. If disabled, each press
+    /// only moves the piece once.
+    pub fn set_auto_repeat_enabled(&mut self, auto_repeat_enabled: bool) {
+        self.auto_repeat_enabled = auto_repeat_enabled;
+    }
+
+    /// Sets the number of ticks after the lock delay expires during which the piece can still be
+    /// moved or rotated. Moves and rotations during this time do not reset the lock delay, so the
+    /// piece locks at the end unless it is moved off of the ground.
+    pub fn set_lock_flash_grace(&mut self, ticks: u32) {
+        self.lock_flash_grace = ticks;
+    }
+
+    /// Sets whether or not gravity moves the piece. If disabled, the piece only falls when soft or
+    /// hard dropped, and only starts locking when soft dropped onto the ground.
+    pub fn set_gravity_enabled(&mut self, gravity_enabled: bool) {
+        self.gravity_enabled = gravity_enabled;
+    }
+
+    /// Sets which moves and rotations reset the lock delay while the piece is on the ground.
+    pub fn set_lock_reset_mode(&mut self, lock_reset_mode: LockResetMode) {
+        self.lock_reset_mode = lock_reset_mode;
+    }
+
+    /// Sets how blocks above cleared rows fall after a line clear.
+    pub fn set_clear_gravity(&mut self, clear_gravity: ClearGravity) {
+        self.clear_gravity = clear_gravity;
+    }
+
+    /// Sets the number of ticks for which a rotation which fails while the piece is falling is
+    /// buffered. If the piece lands on the ground within this time, the rotation is attempted again.
+    /// Setting this to 0 disables buffering.
+    pub fn set_rotation_buffer_ticks(&mut self, ticks: u32) {
+        self.rotation_buffer_ticks = ticks;
+    }
+
+    /// Sets whether or not a piece which collides with the stack when it spawns is pushed up one
+    /// row. The game is only over if the piece also collides after being pushed up.
+    pub fn set_spawn_push_up(&mut self, spawn_push_up: bool) {
+        self.spawn_push_up = spawn_push_up;
+    }
+
+    /// Sets the maximum number of rows a soft drop can move the piece in a single tick, regardless
+    /// of how fast soft drop gravity is. `Option::None` removes the limit. A limit of 0 would
+    /// prevent soft drop from moving the piece at all, so it is treated as no limit.
+    pub fn set_max_soft_drop_rows_per_tick(&mut self, rows: Option<u8>) {
+        self.max_soft_drop_rows_per_tick = rows.filter(|&rows| rows > 0);
+    }
+
+    /// Sets which action takes priority when hold and hard drop are input on the same tick. If
+    /// enabled, the piece is held and the hard drop is ignored. Otherwise, the piece is hard
+    /// dropped and the hold is ignored.
+    pub fn set_hold_over_harddrop(&mut self, hold_over_hard_drop: bool) {
+        self.hold_over_hard_drop = hold_over_hard_drop;
+    }
+
+    /// Sets the number of ticks between soft drops while soft drop is held. A rate of 1 soft drops
+    /// on every tick. A rate of 0 is treated as 1.
+    pub fn set_soft_drop_rate(&mut self, soft_drop_rate: u32) {
+        self.soft_drop_rate = soft_drop_rate.max(1);
+    }
+
+    /// Sets whether or not a move input held while a new piece spawns keeps its auto-repeat charge.
+    /// If disabled, the held input acts as a new press for the new piece.
+    pub fn set_das_carry(&mut self, das_carry: bool) {
+        self.das_carry = das_carry;
+    }
+
+    /// Sets the number of ticks between a piece locking (or lines clearing) and the next piece
+    /// spawning. Holding the hold input while waiting swaps in the hold piece at spawn time.
+    pub fn set_entry_delay(&mut self, entry_delay: u32) {
+        self.entry_delay = entry_delay;
+    }
+
+    /// Returns the current value of every setting.
+    fn settings(&self) -> Settings {
+        Settings {
+            gravity: self.gravity,
+            gravity_enabled: self.gravity_enabled,
+            gravity_direction: self.gravity_direction,
+            spawn_policy: self.spawn_policy,
+            big_mode: self.big_mode,
+            action_order: self.action_order,
+            auto_repeat_enabled: self.auto_repeat_enabled,
+            soft_drop_rate: self.soft_drop_rate,
+            das_carry: self.das_carry,
+            spawn_push_up: self.spawn_push_up,
+            hold_over_hard_drop: self.hold_over_hard_drop,
+            max_soft_drop_rows_per_tick: self.max_soft_drop_rows_per_tick,
+            lock_flash_grace: self.lock_flash_grace,
+            lock_reset_mode: self.lock_reset_mode,
+            clear_gravity: self.clear_gravity,
+            rotation_buffer_ticks: self.rotation_buffer_ticks,
+            entry_delay: self.entry_delay,
+            preview_count: self.preview_count,
+            danger_threshold: self.danger_threshold,
+            garbage_timing: self.garbage_timing,
+        }
+    }
+
+    /// Changes every setting to the specified values. As with the individual setters, the current
+    /// piece is moved to the new spawn position if a setting which affects it changed.
+    fn apply_settings(&mut self, settings: Settings) {
+        let respawn = settings.gravity_direction != self.gravity_direction
+            || settings.spawn_policy != self.spawn_policy
+            || settings.big_mode != self.big_mode;
+        self.gravity = settings.gravity;
+        self.gravity_enabled = settings.gravity_enabled;
+        self.gravity_direction = settings.gravity_direction;
+        self.spawn_policy = settings.spawn_policy;
+        self.big_mode = settings.big_mode;
+        self.action_order = settings.action_order;
+        self.auto_repeat_enabled = settings.auto_repeat_enabled;
+        self.soft_drop_rate = settings.soft_drop_rate;
+        self.das_carry = settings.das_carry;
+        self.spawn_push_up = settings.spawn_push_up;
+        self.hold_over_hard_drop = settings.hold_over_hard_drop;
+        self.max_soft_drop_rows_per_tick = settings.max_soft_drop_rows_per_tick;
+        self.lock_flash_grace = settings.lock_flash_grace;
+        self.lock_reset_mode = settings.lock_reset_mode;
+        self.clear_gravity = settings.clear_gravity;
+        self.rotation_buffer_ticks = settings.rotation_buffer_ticks;
+        self.entry_delay = settings.entry_delay;
+        self.preview_count = settings.preview_count;
+        self.danger_threshold = settings.danger_threshold;
+        self.garbage_timing = settings.garbage_timing;
+        if respawn {
+            self.current_piece = self.spawn_piece(*self.current_piece.piece.get_shape());
+        }
+    }
+
+    /// Immediately rotates the current piece clockwise, applying a wall kick if necessary, without
+    /// waiting for input to be processed on the next tick. Returns whether or not the rotation was
+    /// successful.
+    pub fn try_rotate_cw(&mut self) -> bool {
+        let rotated = self.rotate_piece_cw();
+        if rotated {
+            self.last_action_was_rotation = true;
+        }
+        rotated
+    }
+
+    /// Immediately rotates the current piece counter-clockwise, applying a wall kick if necessary,
+    /// without waiting for input to be processed on the next tick. Returns whether or not the
+    /// rotation was successful.
+    pub fn try_rotate_ccw(&mut self) -> bool {
+        let rotated = self.rotate_piece_ccw();
+        if rotated {
+            self.last_action_was_rotation = true;
+        }
+        rotated
     }
 
     /* * * * * * * * * *
@@ -307,7 +1163,15 @@ impl BaseEngine {
     // Actions performed by the engine.
 
     /// Processes input and returns a list of actions to perform on this tick.
-    fn process_input(&mut self) -> HashSet<Action> {
+    fn process_input(&mut self) -> ActionSet {
+        // Fast path: nothing was input on this tick and nothing was held on the previous tick, so
+        // there are no durations to update and no valid actions.
+        if self.current_tick_inputs.borrow().is_empty()
+            && self.current_inputs.values().all(|duration| *duration == 0)
+        {
+            return ActionSet::default();
+        }
+
         // Clear current_tick_inputs and update current_inputs.
         for action in ALL_ACTIONS.iter() {
             if self.current_tick_inputs.borrow_mut().remove(&action) {
@@ -336,30 +1200,36 @@ impl BaseEngine {
             }
         }
 
-        let mut current_turn_actions = HashSet::new();
+        let mut current_turn_actions = ActionSet::default();
         // Iterate through inputs and determine which actions are valid.
         for (action, duration) in self.current_inputs.iter() {
             use self::Action::*;
             match action {
                 // These actions are only valid on initial press.
-                Hold | RotateClockwise | RotateCounterClockwise | HardDrop => {
+                Hold
+                | RotateClockwise
+                | RotateCounterClockwise
+                | HardDrop
+                | SnapLeft
+                | SnapRight => {
                     if *duration == 1 {
                         current_turn_actions.insert(*action);
                     }
                 }
-                // This is always valid if pressed.
+                // This is valid on first press and on intervals based on the soft drop rate.
                 SoftDrop => {
-                    if *duration >= 1 {
+                    if *duration >= 1 && (*duration - 1) % self.soft_drop_rate == 0 {
                         current_turn_actions.insert(*action);
                     }
                 }
                 // This is valid on first press, when reaching auto-repeat delay,
-                // or on intervals based on the auto-repeat rate.
+                // or on intervals based on the auto-repeat rate, if auto-repeat is enabled.
                 MoveLeft | MoveRight => {
                     if *duration == 1
-                        || *duration == AUTO_REPEAT_DELAY
-                        || *duration > AUTO_REPEAT_DELAY
-                            && (*duration - AUTO_REPEAT_DELAY) % AUTO_REPEAT_RATE == 0
+                        || self.auto_repeat_enabled
+                            && (*duration == AUTO_REPEAT_DELAY
+                                || *duration > AUTO_REPEAT_DELAY
+                                    && (*duration - AUTO_REPEAT_DELAY) % AUTO_REPEAT_RATE == 0)
                     {
                         current_turn_actions.insert(*action);
                     }
@@ -371,17 +1241,52 @@ impl BaseEngine {
     }
 
     fn tick_spawn(&mut self) {
-        self.state = if self.has_collision() {
-            State::TopOut
+        // Holding also spawns a piece, but garbage is only added after a piece locks.
+        if self.garbage_timing == GarbageTiming::OnSpawn && self.locked_since_spawn {
+            self.apply_pending_garbage();
         }
-        else {
-            State::Falling(1)
+        self.locked_since_spawn = false;
+
+        self.state = match self.spawn_position(self.current_piece) {
+            Option::Some(piece) => {
+                self.current_piece = piece;
+                let shape = *self.current_piece.piece.get_shape();
+                self.notify_observers(|obs| obs.on_spawn(shape));
+                State::Falling(1)
+            }
+            Option::None => State::TopOut,
         };
 
+        if !self.das_carry {
+            self.current_inputs.insert(Action::MoveLeft, 0);
+            self.current_inputs.insert(Action::MoveRight, 0);
+        }
+
         self.current_t_spin = TSpinInternal::None;
+        self.last_action_was_rotation = false;
+        self.last_hard_drop = Option::None;
+        self.buffered_rotation = Option::None;
+    }
+
+    fn tick_are(&mut self) {
+        match self.state {
+            State::Are(n) if n >= self.entry_delay => {
+                // Initial hold: if hold is held down at the end of the delay, swap in the hold piece
+                // before it spawns.
+                if self.current_inputs[&Action::Hold] > 0 && self.is_hold_available {
+                    self.hold_piece();
+                    self.is_hold_available = false;
+                }
+                self.state = State::Spawn;
+            }
+            State::Are(n) => {
+                self.state = State::Are(n + 1);
+            }
+            _ => panic!("This method should only be called while state is State::Are."),
+        }
     }
 
-    fn tick_falling(&mut self, actions: &HashSet<Action>) {
+    fn tick_falling(&mut self, actions: &ActionSet) {
         if let State::Falling(n) = self.state {
             let applied_actions = self.apply_actions(&actions);
 
@@ -389,12 +1294,29 @@ impl BaseEngine {
                 self.apply_lock();
             }
             else if applied_actions.contains(&Action::Hold) {
-                self.state = State::Falling(1);
+                // Check whether the new piece can spawn.
+                self.state = State::Spawn;
             }
             else {
+                self.buffer_rotation(actions, &applied_actions);
                 let dropped = self.apply_gravity(&actions);
-                if self.is_in_lock_position() {
+                let can_lock = self.gravity_enabled || actions.contains(&Action::SoftDrop);
+                if can_lock && self.is_in_lock_position() {
                     self.state = State::Lock(1);
+                    if let Option::Some((action, _)) = self.buffered_rotation.take() {
+                        let rotated =
+                            self.apply_piece_rotation(&[action].iter().cloned().collect());
+                        if let Option::Some(Result::Ok(action)) = rotated {
+                            // The rotation was counted as wasted when it was first input and
+                            // failed, so count it as effective instead.
+                            self.wasted_inputs = self.wasted_inputs.saturating_sub(1);
+                            self.effective_inputs += 1;
+                            self.notify_observers(|obs| obs.on_action(action));
+                            if !self.is_in_lock_position() {
+                                self.state = State::Falling(1);
+                            }
+                        }
+                    }
                 }
                 else if dropped {
                     self.state = State::Falling(1);
@@ -409,30 +1331,80 @@ impl BaseEngine {
         }
     }
 
-    fn tick_lock(&mut self, actions: &HashSet<Action>) {
+    /// Buffers a rotation which was input but could not be applied, and expires any previously
+    /// buffered rotation.
+    fn buffer_rotation(&mut self, actions: &ActionSet, applied_actions: &ActionSet) {
+        let failed_rotation = [Action::RotateClockwise, Action::RotateCounterClockwise]
+            .iter()
+            .find(|action| actions.contains(action) && !applied_actions.contains(action));
+
+        self.buffered_rotation = match (failed_rotation, self.buffered_rotation) {
+            (Option::Some(action), _) if self.rotation_buffer_ticks > 0 => {
+                Option::Some((*action, self.rotation_buffer_ticks))
+            }
+            (_, Option::Some((action, ticks))) if ticks > 0 => Option::Some((action, ticks - 1)),
+            _ => Option::None,
+        };
+    }
+
+    fn tick_lock(&mut self, actions: &ActionSet) {
         match self.state {
-            State::Lock(LOCK_DELAY) => {
+            State::Lock(n) if n >= LOCK_DELAY + self.lock_flash_grace => {
                 self.apply_lock();
             }
+            State::Lock(n) if n >= LOCK_DELAY => {
+                // Only moves and rotations are allowed during the grace period.
+                let grace_actions: ActionSet = actions
+                    .iter()
+                    .filter(|action| {
+                        !matches!(action, Action::Hold | Action::SoftDrop | Action::HardDrop)
+                    })
+                    .cloned()
+                    .collect();
+                self.apply_actions(&grace_actions);
+
+                if self.is_in_lock_position() {
+                    self.state = State::Lock(n + 1);
+                }
+                else {
+                    self.state = State::Falling(1);
+                }
+            }
             State::Lock(n) => {
+                let start_row = self.current_piece.lowest_occupied_row();
                 let applied_actions = self.apply_actions(&actions);
 
                 if applied_actions.contains(&Action::Hold) {
-                    self.state = State::Falling(1);
+                    // Check whether the new piece can spawn.
+                    self.state = State::Spawn;
                 }
                 else if applied_actions.contains(&Action::HardDrop) {
                     self.apply_lock();
                 }
                 else if applied_actions.contains(&Action::MoveLeft)
                     || applied_actions.contains(&Action::MoveRight)
+                    || applied_actions.contains(&Action::SnapLeft)
+                    || applied_actions.contains(&Action::SnapRight)
                     || applied_actions.contains(&Action::RotateClockwise)
                     || applied_actions.contains(&Action::RotateCounterClockwise)
                 {
-                    if self.is_in_lock_position() {
+                    let reset = match self.lock_reset_mode {
+                        LockResetMode::Move => true,
+                        LockResetMode::Step => {
+                            let rows = self.current_piece.lowest_occupied_row() - start_row;
+                            rows * self.gravity_direction.row_offset() > 0
+                        }
+                        LockResetMode::None => false,
+                    };
+
+                    if !self.is_in_lock_position() {
+                        self.state = State::Falling(1);
+                    }
+                    else if reset {
                         self.state = State::Lock(1);
                     }
                     else {
-                        self.state = State::Falling(1);
+                        self.state = State::Lock(n + 1);
                     }
                 }
                 else {
@@ -446,10 +1418,9 @@ impl BaseEngine {
     fn tick_line_clear(&mut self) {
         match self.state {
             State::LineClear(LINE_CLEAR_DELAY) => {
-                let n_rows = self.clear_rows();
-                self.notify_observers(|obs| obs.on_line_clear(n_rows));
+                self.clear_rows();
                 self.next_piece();
-                self.state = State::Spawn;
+                self.state = self.entry_state();
             }
             State::LineClear(n) => {
                 self.state = State::LineClear(n + 1);
@@ -458,33 +1429,52 @@ impl BaseEngine {
         }
     }
 
-    fn apply_actions(&mut self, actions: &HashSet<Action>) -> HashSet<Action> {
-        let mut applied_actions = HashSet::new();
+    fn apply_actions(&mut self, actions: &ActionSet) -> ActionSet {
+        let mut applied_actions = ActionSet::default();
 
-        if self.apply_hold(&actions) {
+        // Hold and hard drop are never both applied on the same tick.
+        let hold_allowed = self.hold_over_hard_drop || !actions.contains(&Action::HardDrop);
+        if hold_allowed && self.apply_hold(&actions) {
             applied_actions.insert(Action::Hold);
         }
         else {
-            if let Option::Some(action) = self.apply_piece_move(&actions) {
-                applied_actions.insert(action);
-            }
-            if let Option::Some(action) = self.apply_piece_rotation(&actions) {
-                applied_actions.insert(action);
+            let (move_action, rotation_action) = match self.action_order {
+                ActionOrder::MoveFirst => {
+                    let move_action = self.apply_piece_move(&actions);
+                    (move_action, self.apply_piece_rotation(&actions))
+                }
+                ActionOrder::RotateFirst => {
+                    let rotation_action = self.apply_piece_rotation(&actions);
+                    (self.apply_piece_move(&actions), rotation_action)
+                }
+            };
+            for result in move_action.iter().chain(rotation_action.iter()) {
+                match result {
+                    Result::Ok(action) => {
+                        applied_actions.insert(*action);
+                        self.effective_inputs += 1;
+                    }
+                    Result::Err(_) => self.wasted_inputs += 1,
+                }
             }
             if let Option::Some(action) = self.apply_hard_drop(&actions) {
                 applied_actions.insert(action);
             }
         }
 
+        for action in applied_actions.iter() {
+            self.notify_observers(|obs| obs.on_action(*action));
+        }
         applied_actions
     }
 
     /// Attempts to hold the current piece if it is one of the specified actions.
     /// Returns whether or not the the hold was successful.
-    fn apply_hold(&mut self, actions: &HashSet<Action>) -> bool {
+    fn apply_hold(&mut self, actions: &ActionSet) -> bool {
         if actions.contains(&Action::Hold) && self.is_hold_available {
             self.hold_piece();
             self.is_hold_available = false;
+            self.last_action_was_rotation = false;
             return true;
         }
 
@@ -497,51 +1487,96 @@ impl BaseEngine {
         let current_tetromino = *self.current_piece.piece.get_shape();
 
         match self.hold_piece {
-            Option::Some(piece) => self.current_piece = CurrentPiece::new(piece),
+            Option::Some(piece) => self.current_piece = self.spawn_piece(piece),
             Option::None => self.next_piece(),
         }
         self.hold_piece = Option::Some(current_tetromino);
+
+        let swapped_in = *self.current_piece.piece.get_shape();
+        self.notify_observers(|obs| obs.on_hold(current_tetromino, swapped_in));
     }
 
     /// Applies move if contained in the specified action set.
-    /// Left moves are given priority over right moves.
-    fn apply_piece_move(&mut self, actions: &HashSet<Action>) -> Option<Action> {
+    /// Snaps are given priority over single moves, and left moves are given priority over right
+    /// moves.
+    /// Returns `Result::Ok` with the move which was applied, `Result::Err` with a move which was
+    /// input but could not be applied, or `Option::None` if no move was input.
+    fn apply_piece_move(&mut self, actions: &ActionSet) -> Option<Result<Action, Action>> {
+        let mut failed_move = Option::None;
+        if actions.contains(&Action::SnapLeft) {
+            if self.snap_left() > 0 {
+                return Option::Some(Result::Ok(Action::SnapLeft));
+            }
+            failed_move = Option::Some(Action::SnapLeft);
+        }
+        else if actions.contains(&Action::SnapRight) {
+            if self.snap_right() > 0 {
+                return Option::Some(Result::Ok(Action::SnapRight));
+            }
+            failed_move = Option::Some(Action::SnapRight);
+        }
+
         if actions.contains(&Action::MoveLeft) {
             if self.move_piece(-1) == 1 {
                 self.current_t_spin = TSpinInternal::None;
-                return Option::Some(Action::MoveLeft);
+                self.last_action_was_rotation = false;
+                return Option::Some(Result::Ok(Action::MoveLeft));
             }
+            failed_move = failed_move.or(Option::Some(Action::MoveLeft));
         }
-        else if actions.contains(&Action::MoveRight) && self.move_piece(1) == 1 {
-            self.current_t_spin = TSpinInternal::None;
-            return Option::Some(Action::MoveRight);
+        else if actions.contains(&Action::MoveRight) {
+            if self.move_piece(1) == 1 {
+                self.current_t_spin = TSpinInternal::None;
+                self.last_action_was_rotation = false;
+                return Option::Some(Result::Ok(Action::MoveRight));
+            }
+            failed_move = failed_move.or(Option::Some(Action::MoveRight));
         }
 
-        Option::None
+        failed_move.map(Result::Err)
     }
 
     /// Applies rotation if contained in the specified action set.
     /// Clockwise rotation is given priority over counter-clockwise rotations.
-    fn apply_piece_rotation(&mut self, actions: &HashSet<Action>) -> Option<Action> {
-        if actions.contains(&Action::RotateClockwise) {
-            if self.rotate_piece_cw() {
-                return Option::Some(Action::RotateClockwise);
-            }
+    /// Returns `Result::Ok` with the rotation which was applied, `Result::Err` with a rotation which
+    /// was input but could not be applied, or `Option::None` if no rotation was input.
+    fn apply_piece_rotation(&mut self, actions: &ActionSet) -> Option<Result<Action, Action>> {
+        let (action, rotated) = if actions.contains(&Action::RotateClockwise) {
+            (Action::RotateClockwise, self.rotate_piece_cw())
         }
-        else if actions.contains(&Action::RotateCounterClockwise) && self.rotate_piece_ccw() {
-            return Option::Some(Action::RotateCounterClockwise);
+        else if actions.contains(&Action::RotateCounterClockwise) {
+            (Action::RotateCounterClockwise, self.rotate_piece_ccw())
         }
+        else {
+            return Option::None;
+        };
 
-        Option::None
+        if rotated {
+            self.last_action_was_rotation = true;
+            Option::Some(Result::Ok(action))
+        }
+        else {
+            Option::Some(Result::Err(action))
+        }
     }
 
-    fn apply_hard_drop(&mut self, actions: &HashSet<Action>) -> Option<Action> {
+    fn apply_hard_drop(&mut self, actions: &ActionSet) -> Option<Action> {
         if actions.contains(&Action::HardDrop) {
+            let start_row = self.current_piece.lowest_occupied_row();
             let rows = self.drop(Playfield::TOTAL_HEIGHT);
             if rows > 0 {
                 self.current_t_spin = TSpinInternal::None;
+                self.last_action_was_rotation = false;
             }
 
+            let cells = self.current_piece.get_cells();
+            let cols = cells.iter().map(|&(_, col)| col);
+            self.last_hard_drop = Option::Some(HardDropInfo {
+                start_row,
+                end_row: self.current_piece.lowest_occupied_row(),
+                cols: (cols.clone().min().unwrap(), cols.max().unwrap()),
+            });
+
             self.notify_observers(|obs| obs.on_hard_drop(rows));
             return Option::Some(Action::HardDrop);
         }
@@ -550,8 +1585,12 @@ impl BaseEngine {
     }
 
     /// Applies gravity, given the specified action set.
-    fn apply_gravity(&mut self, actions: &HashSet<Action>) -> bool {
+    fn apply_gravity(&mut self, actions: &ActionSet) -> bool {
         let soft_drop = actions.contains(&Action::SoftDrop);
+        if !self.gravity_enabled && !soft_drop {
+            return false;
+        }
+
         let gravity = if soft_drop {
             self.gravity * 20.
         }
@@ -564,6 +1603,7 @@ impl BaseEngine {
             (State::Falling(n), Gravity::TicksPerRow(tpr)) => {
                 if *n >= u32::from(tpr) {
                     if self.drop_one() == 1 {
+                        self.last_action_was_rotation = false;
                         if soft_drop {
                             self.notify_observers(|obs| obs.on_soft_drop(1));
                         }
@@ -573,8 +1613,13 @@ impl BaseEngine {
                 }
             }
             (State::Falling(_), Gravity::RowsPerTick(rpt)) => {
+                let rpt = match self.max_soft_drop_rows_per_tick {
+                    Option::Some(max) if soft_drop => rpt.min(max),
+                    _ => rpt,
+                };
                 let n_rows = self.drop(rpt);
-                if n_rows > 1 {
+                if n_rows > 0 {
+                    self.last_action_was_rotation = false;
                     if soft_drop {
                         self.notify_observers(|obs| obs.on_soft_drop(n_rows));
                     }
@@ -588,30 +1633,126 @@ impl BaseEngine {
     }
 
     fn apply_lock(&mut self) {
+        let holes = self.playfield.hole_count();
         self.lock();
-        self.notify_observers(|obs| obs.on_lock(TSpin::from(&self.current_t_spin)));
+        self.locked_since_spawn = true;
+        self.holes_created_by_last_lock = self.playfield.hole_count().saturating_sub(holes);
+        // T-spins are only awarded if the last action before locking was a rotation.
+        let t_spin = if self.last_action_was_rotation {
+            TSpin::from(&self.current_t_spin)
+        }
+        else {
+            TSpin::None
+        };
+        self.notify_observers(|obs| obs.on_lock(t_spin, &self.playfield));
         self.current_t_spin = TSpinInternal::None;
+        self.last_action_was_rotation = false;
         if self.contains_full_rows() {
             self.next_piece();
             self.state = State::LineClear(1);
         }
         else {
+            if self.garbage_timing == GarbageTiming::OnLock {
+                self.apply_pending_garbage();
+            }
             self.next_piece();
-            self.state = State::Spawn;
+            self.state = self.entry_state();
+        }
+    }
+
+    /// Adds all pending garbage to the playfield, in the order it was received.
+    fn apply_pending_garbage(&mut self) {
+        while let Option::Some((rows, pattern)) = self.pending_garbage.pop_front() {
+            for hole_col in pattern.hole_cols(rows) {
+                self.playfield.add_garbage_row(hole_col);
+            }
+        }
+    }
+
+    /// Returns the state to enter before the next piece spawns.
+    fn entry_state(&self) -> State {
+        if self.entry_delay > 0 {
+            State::Are(1)
+        }
+        else {
+            State::Spawn
         }
     }
 
     /// Sets the next current piece.
     fn next_piece(&mut self) {
-        self.current_piece = match self.next_pieces.pop_front() {
-            Option::Some(piece) => CurrentPiece::new(piece),
-            Option::None => panic!("This should never happen."),
+        // The queue is only a preview, so it may be empty.
+        let shape = match self.next_pieces.get_mut().pop_front() {
+            Option::Some(piece) => piece,
+            Option::None => self.tetromino_generator.next(),
         };
+        self.current_piece = self.spawn_piece(shape);
 
-        self.next_pieces.push_back(self.tetromino_generator.next());
+        self.fill_next_pieces();
         self.is_hold_available = true;
     }
 
+    /// Generates pieces until the next queue contains at least the preview count. Pieces beyond the
+    /// preview count are kept so that reducing the preview count does not skip any pieces.
+    fn fill_next_pieces(&self) {
+        self.fill_next_pieces_to(self.preview_count);
+    }
+
+    /// Generates pieces until the next queue has at least the specified number of pieces.
+    fn fill_next_pieces_to(&self, count: usize) {
+        // Only borrow mutably if pieces are needed. Once the queue is full, it stays full until the
+        // engine is mutated, so this can not conflict with the borrow held by `NextPiecesIter`.
+        if self.next_pieces.borrow().len() >= count {
+            return;
+        }
+        let mut next_pieces = self.next_pieces.borrow_mut();
+        while next_pieces.len() < count {
+            next_pieces.push_back(self.tetromino_generator.next());
+        }
+    }
+
+    /// Returns the position the specified newly spawned piece starts falling from, which is pushed
+    /// up if its spawn position is blocked and spawn push up is enabled. Returns `Option::None` if
+    /// the piece can not spawn.
+    fn spawn_position(&self, piece: CurrentPiece) -> Option<CurrentPiece> {
+        if !self.has_collision_with_piece(piece) {
+            return Option::Some(piece);
+        }
+        if self.spawn_push_up {
+            let mut pushed_up = piece;
+            pushed_up.row -= self.gravity_direction.row_offset() * piece.scale;
+            if !self.has_collision_with_piece(pushed_up) {
+                return Option::Some(pushed_up);
+            }
+        }
+        Option::None
+    }
+
+    /// Creates a new piece in the spawn position for the current gravity direction.
+    fn spawn_piece(&self, shape: Tetromino) -> CurrentPiece {
+        let mut piece = CurrentPiece::new(shape);
+        if self.gravity_direction == GravityDirection::Up {
+            // Place the bottom of the spawn rotation on the bottom row.
+            piece.row = -1;
+        }
+        else if self.spawn_policy == SpawnPolicy::VisibleTop {
+            // Place the bottom of the spawn rotation on the top visible row.
+            piece.row -= 1;
+        }
+
+        if self.big_mode {
+            // Keep the bottom of the spawn rotation in the same row, and align the piece to a grid
+            // of 2 x 2 squares.
+            piece.scale = 2;
+            piece.row -= 2;
+            if piece.row % 2 == 0 {
+                piece.row -= 1;
+            }
+            piece.col = 2 * spawn_col(Playfield::WIDTH / 2) - 1;
+        }
+        piece
+    }
+
     /// Returns whether or not there is a collision between the playfield and the current piece.
     fn has_collision(&self) -> bool {
         self.has_collision_with_piece(self.current_piece)
@@ -620,28 +1761,16 @@ impl BaseEngine {
     /// Returns whether or not there would be a collision
     /// between the playfield and the specified piece.
     fn has_collision_with_piece(&self, piece: CurrentPiece) -> bool {
-        let bounding_box = piece.piece.get_bounding_box();
-        // Iterate through spaces of bounding box.
-        for (row_offset, bb_row) in bounding_box.iter().enumerate() {
-            for (col_offset, bb_space) in bb_row.iter().enumerate() {
-                // Calculate position of space in playfield.
-                let row = piece.row + row_offset as i8;
-                let col = piece.col + col_offset as i8;
-
-                // Collisions can only occur on blocks.
-                if bb_space == &Space::Block
-                    // Collision occurs if block is outside playfield.
-                    && ((row < 1 || col < 1 || col > Playfield::WIDTH as i8)
-                    // Or if block is inside playfield ...
-                    || (row  >= 1 && col >= 1
-                        // ... and there is already a block in that position.
-                        && self.playfield.get(row as u8, col as u8) == Space::Block))
-                {
-                    return true;
-                }
-            }
+        piece.collides_on(&self.playfield, self.ceiling())
+    }
+
+    /// Returns the highest row a piece can occupy.
+    fn ceiling(&self) -> i8 {
+        // Pieces falling upwards can not go above the visible playfield.
+        match self.gravity_direction {
+            GravityDirection::Down => Playfield::TOTAL_HEIGHT as i8,
+            GravityDirection::Up => Playfield::VISIBLE_HEIGHT as i8,
         }
-        false
     }
 
     /// Drops the current piece by one row if it does not result in a collision.
@@ -651,10 +1780,11 @@ impl BaseEngine {
 
     /// Drops the current piece by up to the specified number of row, or until there is a collision.
     fn drop(&mut self, n_rows: u8) -> u8 {
+        let row_offset = self.gravity_direction.row_offset() * self.current_piece.scale;
         for row in 0..n_rows {
-            self.current_piece.row -= 1;
+            self.current_piece.row += row_offset;
             if self.has_collision() {
-                self.current_piece.row += 1;
+                self.current_piece.row -= row_offset;
                 return row;
             }
         }
@@ -662,97 +1792,61 @@ impl BaseEngine {
         n_rows
     }
 
+    /// Returns the playfield as it would be if the specified piece were locked in its current
+    /// position and any full rows were cleared. Blocks outside of the playfield are ignored.
+    pub fn board_after_clear(&self, piece: CurrentPiece) -> Playfield {
+        let mut playfield = self.playfield.with_piece(piece);
+        remove_full_rows(&mut playfield, self.clear_gravity);
+        playfield
+    }
+
+    /// Returns the position the current piece would be dropped to by a hard drop.
+    fn ghost_piece(&self) -> CurrentPiece {
+        self.current_piece.dropped_on(
+            &self.playfield,
+            self.gravity_direction.row_offset(),
+            self.ceiling(),
+        )
+    }
+
     /// Returns whether or not the current piece is in a position where it can be locked into place.
     fn is_in_lock_position(&self) -> bool {
         let mut piece = self.current_piece;
-        piece.row -= 1;
+        piece.row += self.gravity_direction.row_offset() * piece.scale;
 
         self.has_collision_with_piece(piece)
     }
 
     /// Locks the current piece into it's current location.
     fn lock(&mut self) {
-        let bounding_box = self.current_piece.piece.get_bounding_box();
-        // Iterate through spaces of bounding box.
-        for (row_offset, bb_row) in bounding_box.iter().enumerate() {
-            for (col_offset, bb_space) in bb_row.iter().enumerate() {
-                // Collisions can only occur on blocks.
-                if bb_space == &Space::Block {
-                    // Calculate position of space in playfield.
-                    let row = (self.current_piece.row + row_offset as i8) as u8;
-                    let col = (self.current_piece.col + col_offset as i8) as u8;
-                    self.playfield.set(row as u8, col as u8);
-                }
-            }
+        let shape = *self.current_piece.piece.get_shape();
+        for (row, col) in self.current_piece.get_cells() {
+            self.playfield.set_tetromino(row as u8, col as u8, shape);
         }
     }
 
     /// Returns whether or not at least one row is full.
     fn contains_full_rows(&self) -> bool {
-        for row in 1..=Playfield::TOTAL_HEIGHT {
-            let mut row_full = true;
-            for col in 1..=Playfield::WIDTH {
-                if self.playfield.get(row, col) == Space::Empty {
-                    row_full = false;
-                    break;
-                }
-            }
-            if row_full {
-                return true;
-            }
-        }
-        false
+        !self.playfield.full_rows().is_empty()
     }
 
-    /// Clears any rows that are full and drops blocks down.
+    /// Clears any rows that are full, drops blocks down and notifies observers of each line clear,
+    /// where each cascade is a separate line clear. Returns the total number of rows cleared.
     fn clear_rows(&mut self) -> u8 {
-        // Construct a list of all row that will NOT be cleared.
-        let mut non_full_rows = Vec::with_capacity(Playfield::TOTAL_HEIGHT as usize);
-        for row in 1..=Playfield::TOTAL_HEIGHT {
-            for col in 1..=Playfield::WIDTH {
-                // Any row that has a space will not be cleared.
-                if self.playfield.get(row, col) == Space::Empty {
-                    non_full_rows.push(row);
-                    break;
-                }
-            }
-        }
-
-        // Don't do anything if no rows are full
-        if non_full_rows.len() == Playfield::TOTAL_HEIGHT as usize {
-            return 0;
-        }
-
-        // Copy non-full rows to next available row. Since full rows are not in the list, this has
-        // the effect of overwriting the full rows.
-        let mut current_row = 1;
-        for row in non_full_rows.iter() {
-            // Copy non-full row to current row.
-            for col in 1..=Playfield::WIDTH {
-                match self.playfield.get(*row, col) {
-                    Space::Empty => self.playfield.clear(current_row, col),
-                    Space::Block => self.playfield.set(current_row, col),
-                };
-            }
-            current_row += 1;
-        }
-
-        // Clear remaining rows.
-        for row in current_row..Playfield::TOTAL_HEIGHT {
-            for col in 1..=Playfield::WIDTH {
-                self.playfield.clear(row, col);
-            }
+        let cascades = remove_full_rows(&mut self.playfield, self.clear_gravity);
+        for n_rows in cascades.iter() {
+            self.notify_observers(|obs| obs.on_line_clear(*n_rows));
         }
-
-        Playfield::TOTAL_HEIGHT - non_full_rows.len() as u8
+        cascades.iter().sum()
     }
 
     /// Moves the current piece horizontally by up to the specified amount.
     fn move_piece(&mut self, col_offset: i8) -> u8 {
+        let step = col_offset.signum() * self.current_piece.scale;
         for col in 0..col_offset.abs() {
-            self.current_piece.col += col_offset.signum();
+            self.current_piece.col += step;
             if self.has_collision() {
-                self.current_piece.col -= col_offset.signum();
+                self.current_piece.col -= step;
                 return col as u8;
             }
         }
@@ -779,13 +1873,21 @@ impl BaseEngine {
         rotate(&mut updated_piece);
         let rotated = *updated_piece.piece.get_rotation();
 
-        if let Option::Some((col_offset, row_offset)) =
+        if let Option::Some(((col_offset, row_offset), rotation_point)) =
             self.check_rotation(&mut updated_piece, initial, rotated)
         {
             self.current_piece.col += col_offset;
             self.current_piece.row += row_offset;
             rotate(&mut self.current_piece);
-            self.current_t_spin = self.detect_t_spin();
+            if self.recent_kicks.len() == RECENT_KICK_COUNT {
+                self.recent_kicks.pop_front();
+            }
+            self.recent_kicks
+                .push_back((initial, rotated, (col_offset, row_offset)));
+            if rotation_point == 5 {
+                self.current_t_spin = TSpinInternal::PointFive;
+            }
+            self.current_t_spin = self.detect_t_spin(self.current_piece, self.current_t_spin);
             return true;
         }
 
@@ -794,63 +1896,30 @@ impl BaseEngine {
 
     /// Checks whether or not the specified piece would collide with the playfield.
     /// If it does, attempts to perform a wall kick based on the specified rotation.
-    /// Returns the offset which resulted in no collision as (col_offset, row_offset)
-    /// or `Option::None` if the rotation is not possible.
+    /// Returns the offset which resulted in no collision as (col_offset, row_offset), along with
+    /// the one-based rotation point which was used, or `Option::None` if the rotation is not
+    /// possible. The offset is applied to the specified piece.
     fn check_rotation(
-        &mut self,
+        &self,
         piece: &mut CurrentPiece,
         initial: Rotation,
         rotated: Rotation,
-    ) -> Option<(i8, i8)> {
+    ) -> Option<((i8, i8), usize)> {
         if !self.has_collision_with_piece(*piece) {
-            return Option::Some((0, 0));
-        }
-
-        use super::core::Rotation::*;
-        // A list of (col, row) offsets for the given piece and rotation.
-        let wall_kick_offsets = match piece.piece.get_shape() {
-            // O rotations are identical. Since the piece does not move between rotations,
-            // it cannot collide and should have passed the test above.
-            Tetromino::O => panic!("This should be impossible"),
-            // I has separate different wall kick rules.
-            Tetromino::I => match (initial, rotated) {
-                (Spawn, Clockwise) => vec![(-2, 0), (1, 0), (-2, -1), (1, 2)],
-                (Clockwise, Spawn) => vec![(2, 0), (-1, 0), (2, 1), (-1, -2)],
-                (Clockwise, OneEighty) => vec![(-1, 0), (2, 0), (-1, 2), (2, -1)],
-                (OneEighty, Clockwise) => vec![(1, 0), (-2, 0), (1, -2), (-2, 1)],
-                (OneEighty, CounterClockwise) => vec![(2, 0), (-1, 0), (2, 1), (-1, -2)],
-                (CounterClockwise, OneEighty) => vec![(-2, 0), (1, 0), (-2, -1), (1, 2)],
-                (CounterClockwise, Spawn) => vec![(1, 0), (-2, 0), (1, -2), (-2, 1)],
-                (Spawn, CounterClockwise) => vec![(-1, 0), (2, 0), (-1, 2), (2, -1)],
-                // The only cases left are 180 rotations, which are not supported.
-                _ => panic!("This should be impossible"),
-            },
-            // All other pieces follow the same rules.
-            _ => match (initial, rotated) {
-                (Spawn, Clockwise) => vec![(-1, 0), (-1, 1), (0, -2), (-1, -2)],
-                (Clockwise, Spawn) => vec![(1, 0), (1, -1), (0, 2), (1, 2)],
-                (Clockwise, OneEighty) => vec![(1, 0), (1, -1), (0, 2), (1, 2)],
-                (OneEighty, Clockwise) => vec![(-1, 0), (-1, 1), (0, -2), (-1, -2)],
-                (OneEighty, CounterClockwise) => vec![(1, 0), (1, 1), (0, -2), (1, -2)],
-                (CounterClockwise, OneEighty) => vec![(-1, 0), (-1, -1), (0, 2), (-1, 2)],
-                (CounterClockwise, Spawn) => vec![(-1, 0), (-1, -1), (0, 2), (-1, 2)],
-                (Spawn, CounterClockwise) => vec![(1, 0), (1, 1), (0, -2), (1, -2)],
-                // The only cases left are 180 rotations, which are not supported.
-                _ => panic!("This should be impossible"),
-            },
-        };
+            return Option::Some(((0, 0), 1));
+        }
+
+        let wall_kick_offsets = wall_kick_offsets(*piece.piece.get_shape(), initial, rotated);
 
         // Check each offset.
         for (rotation_point, offset) in wall_kick_offsets.iter().enumerate() {
+            let offset = (offset.0 * piece.scale, offset.1 * piece.scale);
             piece.col += offset.0;
             piece.row += offset.1;
             // Return if there was no collision.
             if !self.has_collision_with_piece(*piece) {
                 // enumerate() uses zero based index. Rotation point use one-based index.
-                if self.current_piece.piece.get_shape() == &Tetromino::T && rotation_point == 4 {
-                    self.current_t_spin = TSpinInternal::PointFive;
-                }
-                return Option::Some(*offset);
+                return Option::Some((offset, rotation_point + 1));
             }
             // Reset position for next test.
             piece.col -= offset.0;
@@ -861,14 +1930,41 @@ impl BaseEngine {
         Option::None
     }
 
-    // Assumes that a rotation has just occurred.
-    fn detect_t_spin(&self) -> TSpinInternal {
-        if self.current_piece.piece.get_shape() != &Tetromino::T {
+    /// Returns the specified piece after rotating it and applying wall kicks, without modifying the
+    /// current piece or t-spin state. Returns `Option::None` if the rotation is not possible.
+    fn rotated_piece<F>(&self, piece: CurrentPiece, rotate: F) -> Option<CurrentPiece>
+    where
+        F: Fn(&mut CurrentPiece),
+    {
+        let initial = *piece.piece.get_rotation();
+        let mut rotated_piece = piece;
+        rotate(&mut rotated_piece);
+        if !self.has_collision_with_piece(rotated_piece) {
+            return Option::Some(rotated_piece);
+        }
+
+        let rotated = *rotated_piece.piece.get_rotation();
+        for offset in wall_kick_offsets(*piece.piece.get_shape(), initial, rotated) {
+            let mut kicked_piece = rotated_piece;
+            kicked_piece.col += offset.0 * piece.scale;
+            kicked_piece.row += offset.1 * piece.scale;
+            if !self.has_collision_with_piece(kicked_piece) {
+                return Option::Some(kicked_piece);
+            }
+        }
+
+        Option::None
+    }
+
+    // Assumes that the specified piece has just been rotated, and that the specified t-spin was
+    // detected before the rotation.
+    fn detect_t_spin(&self, piece: CurrentPiece, previous: TSpinInternal) -> TSpinInternal {
+        if piece.piece.get_shape() != &Tetromino::T {
             return TSpinInternal::None;
         }
 
         // Any further rotation after using rotation point 5 is still considered a T-spin.
-        if self.current_t_spin == TSpinInternal::PointFive {
+        if previous == TSpinInternal::PointFive {
             return TSpinInternal::PointFive;
         }
 
@@ -882,28 +1978,20 @@ impl BaseEngine {
         //     0 1 2 3   0 1 2 3   0 1 2 3   0 1 2 3
 
         // Row/Column offsets for each corner.
-        let (a_offset, b_offset, c_offset, d_offset) = match self.current_piece.piece.get_rotation()
-        {
-            Rotation::Spawn => ((3, 0), (3, 2), (1, 0), (1, 2)),
-            Rotation::Clockwise => ((3, 2), (1, 2), (3, 0), (1, 0)),
-            Rotation::OneEighty => ((1, 2), (1, 0), (3, 2), (3, 0)),
-            Rotation::CounterClockwise => ((1, 0), (3, 0), (1, 2), (3, 2)),
+        let [a_offset, b_offset, c_offset, d_offset] =
+            piece.piece.get_rotation().t_corner_offsets();
+
+        let is_occupied = |(row_offset, col_offset): (i8, i8)| {
+            self.playfield.is_occupied(
+                piece.row + piece.scale * row_offset,
+                piece.col + piece.scale * col_offset,
+            )
         };
 
-        fn is_occupied(engine: &BaseEngine, row_offset: i8, col_offset: i8) -> bool {
-            let current_row = engine.current_piece.row;
-            let current_col = engine.current_piece.col;
-            let row = current_row + row_offset;
-            let col = current_col + col_offset;
-            row < 1 || row > Playfield::TOTAL_HEIGHT as i8
-                || col < 1 || col > Playfield::WIDTH as i8
-                || engine.playfield.get(row as u8, col as u8) == Space::Block
-        }
-
-        let a = is_occupied(self, a_offset.0, a_offset.1);
-        let b = is_occupied(self, b_offset.0, b_offset.1);
-        let c = is_occupied(self, c_offset.0, c_offset.1);
-        let d = is_occupied(self, d_offset.0, d_offset.1);
+        let a = is_occupied(a_offset);
+        let b = is_occupied(b_offset);
+        let c = is_occupied(c_offset);
+        let d = is_occupied(d_offset);
 
         if a && b && (c || d) {
             return TSpinInternal::Regular;
@@ -925,8 +2013,77 @@ impl BaseEngine {
     }
 }
 
+/// An iterator over the visible pieces in the next queue.
+struct NextPiecesIter<'a> {
+    next_pieces: Ref<'a, VecDeque<Tetromino>>,
+    index: usize,
+    count: usize,
+}
+
+impl Iterator for NextPiecesIter<'_> {
+    type Item = Tetromino;
+
+    fn next(&mut self) -> Option<Tetromino> {
+        if self.index >= self.count {
+            return Option::None;
+        }
+        let piece = self.next_pieces.get(self.index).cloned();
+        self.index += 1;
+        piece
+    }
+}
+
+/// Returns the wall kick offsets, as (col_offset, row_offset), to test in order when a piece with
+/// the specified shape is rotated from the initial rotation to the rotated rotation.
+fn wall_kick_offsets(shape: Tetromino, initial: Rotation, rotated: Rotation) -> Vec<(i8, i8)> {
+    use super::core::Rotation::*;
+    match shape {
+        // O rotations are identical. Since the piece does not move between rotations,
+        // it cannot collide after rotating and never needs a wall kick.
+        Tetromino::O => panic!("This should be impossible"),
+        // I has separate different wall kick rules.
+        Tetromino::I => match (initial, rotated) {
+            (Spawn, Clockwise) => vec![(-2, 0), (1, 0), (-2, -1), (1, 2)],
+            (Clockwise, Spawn) => vec![(2, 0), (-1, 0), (2, 1), (-1, -2)],
+            (Clockwise, OneEighty) => vec![(-1, 0), (2, 0), (-1, 2), (2, -1)],
+            (OneEighty, Clockwise) => vec![(1, 0), (-2, 0), (1, -2), (-2, 1)],
+            (OneEighty, CounterClockwise) => vec![(2, 0), (-1, 0), (2, 1), (-1, -2)],
+            (CounterClockwise, OneEighty) => vec![(-2, 0), (1, 0), (-2, -1), (1, 2)],
+            (CounterClockwise, Spawn) => vec![(1, 0), (-2, 0), (1, -2), (-2, 1)],
+            (Spawn, CounterClockwise) => vec![(-1, 0), (2, 0), (-1, 2), (2, -1)],
+            // The only cases left are 180 rotations, which are not supported.
+            _ => panic!("This should be impossible"),
+        },
+        // All other pieces follow the same rules.
+        _ => match (initial, rotated) {
+            (Spawn, Clockwise) => vec![(-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            (Clockwise, Spawn) => vec![(1, 0), (1, -1), (0, 2), (1, 2)],
+            (Clockwise, OneEighty) => vec![(1, 0), (1, -1), (0, 2), (1, 2)],
+            (OneEighty, Clockwise) => vec![(-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            (OneEighty, CounterClockwise) => vec![(1, 0), (1, 1), (0, -2), (1, -2)],
+            (CounterClockwise, OneEighty) => vec![(-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            (CounterClockwise, Spawn) => vec![(-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            (Spawn, CounterClockwise) => vec![(1, 0), (1, 1), (0, -2), (1, -2)],
+            // The only cases left are 180 rotations, which are not supported.
+            _ => panic!("This should be impossible"),
+        },
+    }
+}
+
 trait TetrominoGenerator {
     fn next(&self) -> Tetromino;
+
+    /// Returns this generator as a `Reseedable`, if it can be reseeded.
+    fn as_reseedable(&self) -> Option<&dyn Reseedable> {
+        Option::None
+    }
+}
+
+/// A tetromino generator whose random number generator can be reseeded.
+trait Reseedable {
+    /// Reseeds the random number generator. Pieces generated after reseeding only depend on the
+    /// new seed.
+    fn reseed(&self, seed: u64);
 }
 
 struct BagGenerator {
@@ -936,13 +2093,13 @@ struct BagGenerator {
 impl BagGenerator {
     fn new() -> BagGenerator {
         let mut bag = VecDeque::with_capacity(7);
-        bag.extend(BagGenerator::new_bag().iter());
+        bag.extend(BagGenerator::new_bag(&mut rand::thread_rng()).iter());
         BagGenerator {
             bag: RefCell::from(bag),
         }
     }
 
-    fn new_bag() -> [Tetromino; 7] {
+    fn new_bag<R: Rng>(rng: &mut R) -> [Tetromino; 7] {
         let mut bag = [
             Tetromino::I,
             Tetromino::O,
@@ -952,7 +2109,7 @@ impl BagGenerator {
             Tetromino::J,
             Tetromino::L,
         ];
-        rand::thread_rng().shuffle(&mut bag);
+        rng.shuffle(&mut bag);
         bag
     }
 }
@@ -960,12 +2117,288 @@ impl BagGenerator {
 impl TetrominoGenerator for BagGenerator {
     fn next(&self) -> Tetromino {
         if self.bag.borrow().is_empty() {
-            self.bag.borrow_mut().extend(BagGenerator::new_bag().iter());
+            self.bag
+                .borrow_mut()
+                .extend(BagGenerator::new_bag(&mut rand::thread_rng()).iter());
+        }
+
+        // Since we fill the bag if it is empty, pop_front should always return Option::Some.
+        self.bag.borrow_mut().pop_front().unwrap()
+    }
+}
+
+/// Wraps another generator and, in debug builds, checks that every generated tetromino is valid.
+struct ValidatingGenerator {
+    generator: Box<dyn TetrominoGenerator>,
+}
+
+impl TetrominoGenerator for ValidatingGenerator {
+    fn next(&self) -> Tetromino {
+        let tetromino = self.generator.next();
+        debug_assert!(
+            tetromino.index() < 7,
+            "generator produced an invalid tetromino: {:?}",
+            tetromino
+        );
+        tetromino
+    }
+
+    fn as_reseedable(&self) -> Option<&dyn Reseedable> {
+        self.generator.as_reseedable()
+    }
+}
+
+/// Generates tetrominoes from a fixed sequence, repeating it when it runs out.
+struct SequenceGenerator {
+    sequence: Vec<Tetromino>,
+    index: RefCell<usize>,
+}
+
+impl SequenceGenerator {
+    fn new(sequence: &[Tetromino]) -> SequenceGenerator {
+        SequenceGenerator {
+            sequence: sequence.to_vec(),
+            index: RefCell::new(0),
+        }
+    }
+}
+
+impl TetrominoGenerator for SequenceGenerator {
+    fn next(&self) -> Tetromino {
+        let mut index = self.index.borrow_mut();
+        let tetromino = self.sequence[*index % self.sequence.len()];
+        *index += 1;
+        tetromino
+    }
+}
+
+/// Wraps another generator and records every tetromino it generates.
+struct RecordingGenerator {
+    generator: Box<dyn TetrominoGenerator>,
+    pieces: Rc<RefCell<Vec<Tetromino>>>,
+}
+
+impl TetrominoGenerator for RecordingGenerator {
+    fn next(&self) -> Tetromino {
+        let tetromino = self.generator.next();
+        self.pieces.borrow_mut().push(tetromino);
+        tetromino
+    }
+
+    fn as_reseedable(&self) -> Option<&dyn Reseedable> {
+        self.generator.as_reseedable()
+    }
+}
+
+/// The settings of an engine which affect how it responds to input, so that a replay behaves the
+/// same as the recorded game.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Settings {
+    gravity: Gravity,
+    gravity_enabled: bool,
+    gravity_direction: GravityDirection,
+    spawn_policy: SpawnPolicy,
+    big_mode: bool,
+    action_order: ActionOrder,
+    auto_repeat_enabled: bool,
+    soft_drop_rate: u32,
+    das_carry: bool,
+    spawn_push_up: bool,
+    hold_over_hard_drop: bool,
+    max_soft_drop_rows_per_tick: Option<u8>,
+    lock_flash_grace: u32,
+    lock_reset_mode: LockResetMode,
+    clear_gravity: ClearGravity,
+    rotation_buffer_ticks: u32,
+    entry_delay: u32,
+    preview_count: usize,
+    danger_threshold: u8,
+    garbage_timing: GarbageTiming,
+}
+
+/// The pieces generated, inputs received, and settings used by an engine created with
+/// `BaseEngine::with_recording`.
+struct Recording {
+    pieces: Rc<RefCell<Vec<Tetromino>>>,
+    inputs: Vec<ActionSet>,
+    // The settings in effect from each tick, recorded whenever they change.
+    settings: Vec<(usize, Settings)>,
+}
+
+impl Recording {
+    /// Records the settings and input for the next tick.
+    fn record_tick(&mut self, settings: Settings, inputs: ActionSet) {
+        if self.settings.last().map(|(_, last)| *last) != Option::Some(settings) {
+            self.settings.push((self.inputs.len(), settings));
+        }
+        self.inputs.push(inputs);
+    }
+}
+
+/// A recorded game, consisting of every generated piece, the input on every tick, and the engine
+/// settings.
+pub struct Replay {
+    pieces: Vec<Tetromino>,
+    inputs: Vec<ActionSet>,
+    settings: Vec<(usize, Settings)>,
+}
+
+impl Replay {
+    /// Creates a replay of the specified game so far. Returns `Option::None` if the engine was not
+    /// created with `BaseEngine::with_recording`.
+    pub fn from_game(engine: &BaseEngine) -> Option<Replay> {
+        engine.recording.as_ref().map(|recording| {
+            let mut settings = recording.settings.clone();
+            if settings.is_empty() {
+                settings.push((0, engine.settings()));
+            }
+            Replay {
+                pieces: recording.pieces.borrow().clone(),
+                inputs: recording.inputs.clone(),
+                settings,
+            }
+        })
+    }
+
+    /// Returns every piece generated during the game, in order.
+    pub fn get_pieces(&self) -> &[Tetromino] {
+        &self.pieces
+    }
+
+    /// Returns the number of recorded ticks.
+    pub fn tick_count(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Creates a new engine with the recorded settings which generates the recorded pieces. The
+    /// engine is in its initial state; use `play` to also apply the recorded input.
+    pub fn engine(&self) -> BaseEngine {
+        let mut engine = BaseEngine::with_sequence(&self.pieces);
+        engine.apply_settings(self.settings[0].1);
+        engine
+    }
+
+    /// Replays the game, returning an engine in the same state as the recorded game. Settings
+    /// which changed during the game are changed on the same tick.
+    pub fn play(&self) -> BaseEngine {
+        let mut engine = self.engine();
+        for (tick, inputs) in self.inputs.iter().enumerate() {
+            let changed = self
+                .settings
+                .iter()
+                .find(|(from_tick, _)| *from_tick == tick);
+            if let Option::Some((_, settings)) = changed {
+                engine.apply_settings(*settings);
+            }
+            engine.tick_with_action_set(*inputs);
+        }
+        engine
+    }
+}
+
+/// An observer which records a hash of the playfield and current piece after every tick. Engines
+/// which receive the same pieces and inputs record the same hashes, so comparing them can be used
+/// to detect desyncs. The hash is 64-bit FNV-1a over a fixed encoding of the blocks and piece, so it
+/// is the same across platforms and compiler versions.
+#[derive(Default)]
+pub struct HashRecorder {
+    hashes: RefCell<Vec<u64>>,
+}
+
+impl HashRecorder {
+    pub fn new() -> HashRecorder {
+        HashRecorder {
+            hashes: RefCell::new(vec![]),
+        }
+    }
+
+    /// Returns the hash recorded on every tick so far, in order.
+    pub fn get_hashes(&self) -> Vec<u64> {
+        self.hashes.borrow().clone()
+    }
+}
+
+impl BaseEngineObserver for HashRecorder {
+    fn on_tick(&self, playfield: &Playfield, current_piece: CurrentPiece) {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        // Each space is 0 if empty, or 1 more than its color index.
+        let spaces = (1..=Playfield::TOTAL_HEIGHT).flat_map(|row| {
+            (1..=Playfield::WIDTH).map(move |col| match playfield.color_index(row, col) {
+                Option::Some(index) => index + 1,
+                Option::None => 0,
+            })
+        });
+        let rotation = match current_piece.piece.get_rotation() {
+            Rotation::Spawn => 0,
+            Rotation::Clockwise => 1,
+            Rotation::OneEighty => 2,
+            Rotation::CounterClockwise => 3,
+        };
+        let piece = [
+            current_piece.piece.get_shape().index(),
+            rotation,
+            current_piece.row as u8,
+            current_piece.col as u8,
+            current_piece.scale as u8,
+        ];
+
+        let hash = spaces
+            .chain(piece.iter().cloned())
+            .fold(FNV_OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+            });
+        self.hashes.borrow_mut().push(hash);
+    }
+}
+
+/// Creates a random number generator from a 64-bit seed. The same seed always creates a generator
+/// which produces the same sequence.
+pub(crate) fn seeded_rng(seed: u64) -> StdRng {
+    let mut bytes = [0u8; 32];
+    bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    StdRng::from_seed(bytes)
+}
+
+/// A bag generator which uses a seeded random number generator, so that the same seed always
+/// generates the same sequence.
+struct SeededBagGenerator {
+    rng: RefCell<StdRng>,
+    bag: RefCell<VecDeque<Tetromino>>,
+}
+
+impl SeededBagGenerator {
+    fn new(seed: u64) -> SeededBagGenerator {
+        SeededBagGenerator {
+            rng: RefCell::new(seeded_rng(seed)),
+            bag: RefCell::new(VecDeque::with_capacity(7)),
+        }
+    }
+}
+
+impl TetrominoGenerator for SeededBagGenerator {
+    fn next(&self) -> Tetromino {
+        if self.bag.borrow().is_empty() {
+            let bag = BagGenerator::new_bag(&mut *self.rng.borrow_mut());
+            self.bag.borrow_mut().extend(bag.iter());
         }
 
         // Since we fill the bag if it is empty, pop_front should always return Option::Some.
         self.bag.borrow_mut().pop_front().unwrap()
     }
+
+    fn as_reseedable(&self) -> Option<&dyn Reseedable> {
+        Option::Some(self)
+    }
+}
+
+impl Reseedable for SeededBagGenerator {
+    fn reseed(&self, seed: u64) {
+        // Discard the rest of the current bag so that the next piece starts a bag from the new seed.
+        self.bag.borrow_mut().clear();
+        *self.rng.borrow_mut() = seeded_rng(seed);
+    }
 }
 
 impl Distribution<Tetromino> for Standard {
@@ -986,21 +2419,7 @@ impl Distribution<Tetromino> for Standard {
 
 impl fmt::Debug for BaseEngine {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut playfield = self.playfield;
-
-        let bounding_box = self.current_piece.piece.get_bounding_box();
-        for (row_offset, bb_row) in bounding_box.iter().enumerate() {
-            for (col_offset, bb_space) in bb_row.iter().enumerate() {
-                // Calculate position of space in playfield.
-                let row = self.current_piece.row + row_offset as i8;
-                let col = self.current_piece.col + col_offset as i8;
-                if bb_space == &Space::Block {
-                    playfield.set(row as u8, col as u8);
-                }
-            }
-        }
-
-        write!(f, "{:?}", playfield)
+        write!(f, "{:?}", self.playfield.with_piece(self.current_piece))
     }
 }
 
@@ -1035,22 +2454,92 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_engine_new() {
-        let engine = BaseEngine::new();
+    /// Records notifications from the engine.
+    struct RecordingObserver {
+        locks: RefCell<Vec<TSpin>>,
+        holds: RefCell<Vec<(Tetromino, Tetromino)>>,
+        soft_drops: RefCell<Vec<u8>>,
+        line_clears: RefCell<Vec<u8>>,
+    }
 
-        // Playfield should start empty.
-        for row in 1..=Playfield::TOTAL_HEIGHT {
-            for col in 1..=Playfield::WIDTH {
-                assert_eq!(engine.playfield.get(row, col), Space::Empty);
+    impl RecordingObserver {
+        fn new() -> RecordingObserver {
+            RecordingObserver {
+                locks: RefCell::new(vec![]),
+                holds: RefCell::new(vec![]),
+                soft_drops: RefCell::new(vec![]),
+                line_clears: RefCell::new(vec![]),
             }
         }
-
-        // Current piece should be in spawn rotation.
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
     }
 
-    #[test]
+    impl BaseEngineObserver for RecordingObserver {
+        fn on_lock(&self, t_spin: TSpin, _playfield: &Playfield) {
+            self.locks.borrow_mut().push(t_spin);
+        }
+
+        fn on_hold(&self, swapped_out: Tetromino, swapped_in: Tetromino) {
+            self.holds.borrow_mut().push((swapped_out, swapped_in));
+        }
+
+        fn on_soft_drop(&self, n_rows: u8) {
+            self.soft_drops.borrow_mut().push(n_rows);
+        }
+
+        fn on_line_clear(&self, n_rows: u8) {
+            self.line_clears.borrow_mut().push(n_rows);
+        }
+    }
+
+    /// Returns the specified actions as a `HashSet` or `ActionSet`.
+    fn actions<T: FromIterator<Action>>(actions: &[Action]) -> T {
+        actions.iter().cloned().collect()
+    }
+
+    /// Inputs a random subset of actions then ticks the engine.
+    fn fuzz_tick<R: Rng>(engine: &mut BaseEngine, rng: &mut R) -> State {
+        // Hard drop and hold are less likely, so that pieces spend time falling and locking.
+        if rng.gen_bool(0.3) {
+            engine.input_move_left();
+        }
+        if rng.gen_bool(0.3) {
+            engine.input_move_right();
+        }
+        if rng.gen_bool(0.2) {
+            engine.input_rotate_cw();
+        }
+        if rng.gen_bool(0.2) {
+            engine.input_rotate_ccw();
+        }
+        if rng.gen_bool(0.3) {
+            engine.input_soft_drop();
+        }
+        if rng.gen_bool(0.02) {
+            engine.input_hard_drop();
+        }
+        if rng.gen_bool(0.02) {
+            engine.input_hold();
+        }
+
+        engine.tick()
+    }
+
+    #[test]
+    fn test_engine_new() {
+        let engine = BaseEngine::new();
+
+        // Playfield should start empty.
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(engine.playfield.get(row, col), Space::Empty);
+            }
+        }
+
+        // Current piece should be in spawn rotation.
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+    }
+
+    #[test]
     fn test_current_piece_new() {
         assert_current_piece_new(CurrentPiece::new(Tetromino::I), Tetromino::I);
         assert_current_piece_new(CurrentPiece::new(Tetromino::O), Tetromino::O);
@@ -1068,6 +2557,51 @@ mod tests {
         assert_eq!(piece.col, 4);
     }
 
+    #[test]
+    fn test_spawn_col() {
+        fn cols(shape: Tetromino, width: u8) -> (i8, i8) {
+            let mut piece = CurrentPiece::new(shape);
+            piece.set_position(piece.get_row(), spawn_col(width));
+            let cols: Vec<i8> = piece.get_cells().iter().map(|(_, col)| *col).collect();
+            (*cols.iter().min().unwrap(), *cols.iter().max().unwrap())
+        }
+
+        assert_eq!(spawn_col(Playfield::WIDTH), 4);
+        // O and I pieces are centered.
+        assert_eq!(cols(Tetromino::O, 10), (5, 6));
+        assert_eq!(cols(Tetromino::I, 10), (4, 7));
+        assert_eq!(cols(Tetromino::O, 6), (3, 4));
+        assert_eq!(cols(Tetromino::I, 6), (2, 5));
+        // Other pieces are left of center.
+        assert_eq!(cols(Tetromino::T, 10), (4, 6));
+        assert_eq!(cols(Tetromino::T, 6), (2, 4));
+    }
+
+    #[test]
+    fn test_current_piece_lowest_occupied_row() {
+        // The bottom two rows of the I piece's bounding box are empty in spawn rotation.
+        let mut piece = CurrentPiece::new(Tetromino::I);
+        assert_eq!(piece.lowest_occupied_row(), piece.get_row() + 2);
+        piece.piece.rotate_cw();
+        assert_eq!(piece.lowest_occupied_row(), piece.get_row());
+
+        piece.scale = 2;
+        piece.piece.rotate_cw();
+        assert_eq!(piece.lowest_occupied_row(), piece.get_row() + 2);
+        for (row, _) in piece.get_cells() {
+            assert!(row >= piece.lowest_occupied_row());
+        }
+    }
+
+    #[test]
+    fn test_current_piece_set_position() {
+        let mut piece = CurrentPiece::new(Tetromino::T);
+        piece.set_position(-1, 8);
+        assert_eq!(piece.get_row(), -1);
+        assert_eq!(piece.get_col(), 8);
+        assert_eq!(piece.get_piece(), Piece::new(Tetromino::T));
+    }
+
     #[test]
     fn test_engine_next_piece() {
         let mut engine = BaseEngine::new();
@@ -1167,6 +2701,47 @@ mod tests {
         assert_eq!(engine.current_piece.row, start_row - 5);
     }
 
+    #[test]
+    fn test_engine_get_playfield() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        assert_eq!(engine.get_playfield().highest_block_row(), Option::None);
+
+        engine.input_hard_drop();
+        engine.tick();
+        let playfield = engine.get_playfield();
+        assert_eq!(playfield.get_tetromino(1, 5), Option::Some(Tetromino::O));
+        assert_eq!(playfield.highest_block_row(), Option::Some(2));
+    }
+
+    #[test]
+    fn test_engine_block_count() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        assert_eq!(engine.playfield.block_count(), 0);
+
+        // Lock two horizontal I pieces in the bottom row.
+        engine.next_piece();
+        engine.move_piece(-10);
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        engine.lock();
+        assert_eq!(engine.playfield.block_count(), 4);
+
+        engine.next_piece();
+        engine.move_piece(10);
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        engine.lock();
+        assert_eq!(engine.playfield.block_count(), 8);
+
+        // Fill the rest of the bottom row, plus one block above it, then clear it.
+        engine.playfield.set(1, 5);
+        engine.playfield.set(1, 6);
+        engine.playfield.set(2, 1);
+        assert_eq!(engine.playfield.block_count(), 11);
+        assert_eq!(engine.clear_rows(), 1);
+        assert_eq!(engine.playfield.block_count(), 1);
+    }
+
     #[test]
     fn test_engine_lock() {
         let mut engine =
@@ -1209,239 +2784,2325 @@ mod tests {
     }
 
     #[test]
-    fn test_clear_rows() {
-        let mut engine = BaseEngine::new();
+    fn test_engine_lock_after_floor_kick_lifts_piece() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
 
-        // Fill first, second, and fourth row.
-        for col in 1..=Playfield::WIDTH {
-            engine.playfield.set(1, col);
-            engine.playfield.set(2, col);
-            engine.playfield.set(4, col);
+        // Place a clockwise T on the floor. Rotating back to spawn is blocked in place and by the
+        // first two kicks, so the third kick lifts the piece two rows, off the ground.
+        // 3 ----T-----
+        // 2 ---#TT#---
+        // 1 ----T#----
+        // 0 1234567890
+        engine.playfield.set(2, 4);
+        engine.playfield.set(2, 7);
+        engine.playfield.set(1, 6);
+        engine.current_piece.piece.rotate_cw();
+        engine.current_piece.row = 0;
+        engine.current_piece.col = 4;
+
+        engine.tick();
+        engine.tick();
+        assert!(matches!(engine.state, State::Lock(2)));
+
+        engine.input_rotate_ccw();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        assert_eq!((engine.current_piece.row, engine.current_piece.col), (2, 4));
+        assert!(matches!(engine.state, State::Falling(1)));
+
+        // The piece falls one row onto the blocks and the lock delay starts over.
+        let mut ticks = 0;
+        while let State::Falling(_) = engine.state {
+            engine.tick();
+            ticks += 1;
+            assert!(ticks <= 30);
         }
-        // Fill miscellaneous spaces in other rows.
-        engine.playfield.set(3, 3);
-        engine.playfield.set(3, 6);
-        engine.playfield.set(5, 1);
-        engine.playfield.set(6, 4);
-        engine.playfield.set(6, 10);
-        engine.playfield.set(7, 2);
-        engine.playfield.set(7, 5);
-        engine.playfield.set(7, 7);
-        engine.playfield.set(8, 9);
-
-        // Playfield should now look like this (ignoring empty rows).
-        // 8 --------#-
-        // 7 -#--#-#---
-        // 6 ---#-----#
-        // 5 #---------
-        // 4 ##########
-        // 3 --#--#----
-        // 2 ##########
-        // 1 ##########
-        //   1234567890
-
-        engine.clear_rows();
-        // Playfield should now look like this (ignoring empty rows).
-        // 5 --------#-
-        // 4 -#--#-#---
-        // 3 ---#-----#
-        // 2 #---------
-        // 1 --#--#----
-        //   1234567890
-        assert_eq!(engine.playfield.get(1, 3), Space::Block);
-        assert_eq!(engine.playfield.get(1, 6), Space::Block);
-        assert_eq!(engine.playfield.get(2, 1), Space::Block);
-        assert_eq!(engine.playfield.get(3, 4), Space::Block);
-        assert_eq!(engine.playfield.get(3, 10), Space::Block);
-        assert_eq!(engine.playfield.get(4, 2), Space::Block);
-        assert_eq!(engine.playfield.get(4, 5), Space::Block);
-        assert_eq!(engine.playfield.get(4, 7), Space::Block);
-        assert_eq!(engine.playfield.get(5, 9), Space::Block);
+        assert!(matches!(engine.state, State::Lock(1)));
+        assert_eq!(engine.current_piece.row, 1);
 
-        // Rows above should be empty.
-        for row in 6..=8 {
-            for col in 1..Playfield::WIDTH {
-                assert_eq!(engine.playfield.get(row, col), Space::Empty);
-            }
+        // Only grounded ticks count towards the lock delay.
+        for _ in 1..LOCK_DELAY {
+            engine.tick();
         }
+        assert!(matches!(engine.state, State::Lock(LOCK_DELAY)));
+        engine.tick();
+        assert_eq!(engine.playfield.get(3, 5), Space::Block);
+        assert_eq!(engine.playfield.get(4, 5), Space::Block);
     }
 
     #[test]
-    fn test_engine_rotate_piece() {
+    fn test_engine_get_active_pieces() {
         let mut engine = BaseEngine::new();
+        for col in 1..=Playfield::WIDTH {
+            engine.playfield.set(5, col);
+        }
 
-        // Rotate clockwise.
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
-        engine.rotate_piece_cw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::Clockwise
-        );
-        engine.rotate_piece_cw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::OneEighty
-        );
-        engine.rotate_piece_cw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::CounterClockwise
-        );
-        engine.rotate_piece_cw();
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        let (current, ghost) = engine.get_active_pieces();
+        assert_eq!(current, engine.current_piece);
+        assert_eq!(ghost.piece, current.piece);
+        assert_eq!(ghost.col, current.col);
 
-        // Rotate counter-clockwise.
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
-        engine.rotate_piece_ccw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::CounterClockwise
-        );
-        engine.rotate_piece_ccw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::OneEighty
-        );
-        engine.rotate_piece_ccw();
-        assert_eq!(
-            engine.current_piece.piece.get_rotation(),
-            &Rotation::Clockwise
-        );
-        engine.rotate_piece_ccw();
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        // The ghost lands on the blocks, in the same position as a hard drop.
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        assert_eq!(ghost.row, engine.current_piece.row);
+        assert!(ghost.get_cells().iter().any(|&(row, _)| row == 6));
     }
 
     #[test]
-    fn test_engine_rotate_piece_collision() {
+    fn test_engine_last_hard_drop() {
         let mut engine =
             BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
-        engine.next_piece();
-
-        // Surround above and below to prevent rotation.
-        for col in 4..=7 {
-            engine.playfield.set(20, col);
-            engine.playfield.set(22, col);
-        }
+        assert_eq!(engine.get_last_hard_drop(), Option::None);
 
-        // attempt rotate
-        engine.rotate_piece_cw();
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        // I piece in spawn position occupies row 21 and columns 4 to 7.
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(
+            engine.get_last_hard_drop(),
+            Option::Some(HardDropInfo {
+                start_row: 21,
+                end_row: 1,
+                cols: (4, 7),
+            })
+        );
 
-        engine.rotate_piece_ccw();
-        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        // Cleared when the next piece spawns.
+        engine.tick();
+        assert_eq!(engine.get_last_hard_drop(), Option::None);
     }
 
     #[test]
-    fn test_engine_rotate_piece_wall_kick() {
+    fn test_engine_hard_drop_score_preview() {
         let mut engine =
-            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
-        engine.next_piece();
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        // I piece in spawn position occupies row 21 and falls to row 1.
+        assert_eq!(engine.hard_drop_distance(), 20);
+        assert_eq!(engine.hard_drop_score_preview(), 40);
+
+        let preview = engine.hard_drop_score_preview();
+        engine.input_hard_drop();
+        engine.tick();
+        let info = engine.get_last_hard_drop().unwrap();
+        assert_eq!(preview, 2 * (info.start_row - info.end_row) as u32);
+
+        // The next I piece lands on the first.
+        engine.tick();
+        assert_eq!(engine.hard_drop_distance(), 19);
+        assert_eq!(engine.hard_drop_score_preview(), 38);
+    }
 
-        // Setup wall kick
-        // T---------
-        // TT#-------
-        // T--#------
-        engine.playfield.set(1, 4);
-        engine.playfield.set(2, 3);
-        engine.rotate_piece_cw();
-        engine.move_piece(-10);
-        engine.drop(Playfield::VISIBLE_HEIGHT);
+    #[test]
+    fn test_garbage_pattern_clean() {
+        assert_eq!(GarbagePattern::Clean(3).hole_cols(4), vec![3, 3, 3, 3]);
+        assert!(GarbagePattern::Clean(3).hole_cols(0).is_empty());
+    }
 
-        // Perform wall kick and lock into place.
-        // ----------
-        // -T#-------
-        // TTT#------
-        engine.rotate_piece_ccw();
-        engine.lock();
+    #[test]
+    fn test_garbage_pattern_cheese() {
+        let cols = GarbagePattern::Cheese(0).hole_cols(100);
+        assert_eq!(cols.len(), 100);
+        for col in cols.iter() {
+            assert!((1..=Playfield::WIDTH).contains(col));
+        }
+        for pair in cols.windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
 
-        // Check that piece in expected position.
-        assert_eq!(engine.playfield.get(1, 1), Space::Block);
-        assert_eq!(engine.playfield.get(1, 2), Space::Block);
-        assert_eq!(engine.playfield.get(1, 3), Space::Block);
-        assert_eq!(engine.playfield.get(2, 2), Space::Block);
+        // The same seed always generates the same holes.
+        assert_eq!(GarbagePattern::Cheese(0).hole_cols(100), cols);
     }
 
     #[test]
-    fn test_engine_move_piece() {
-        let mut engine = BaseEngine::new();
+    fn test_engine_add_garbage_pattern() {
+        fn hole_cols(engine: &BaseEngine, rows: u8) -> Vec<u8> {
+            (1..=rows)
+                .map(|row| {
+                    let holes: Vec<u8> = (1..=Playfield::WIDTH)
+                        .filter(|col| engine.playfield.get(row, *col) == Space::Empty)
+                        .collect();
+                    assert_eq!(holes.len(), 1);
+                    holes[0]
+                })
+                .collect()
+        }
 
-        // Test move left.
-        let start_col = engine.current_piece.col;
-        engine.move_piece(-1);
-        assert_eq!(engine.current_piece.col, start_col - 1);
-        engine.move_piece(-1);
-        assert_eq!(engine.current_piece.col, start_col - 2);
-        engine.move_piece(-1);
-        assert_eq!(engine.current_piece.col, start_col - 3);
+        let mut engine = BaseEngine::new();
+        engine.add_garbage(4, 2);
+        assert_eq!(hole_cols(&engine, 4), vec![2; 4]);
+        assert_eq!(engine.playfield.get(5, 1), Space::Empty);
 
-        // Spawn a new piece then test move right.
-        let start_col = engine.current_piece.col;
-        engine.move_piece(1);
-        assert_eq!(engine.current_piece.col, start_col + 1);
-        engine.move_piece(1);
-        assert_eq!(engine.current_piece.col, start_col + 2);
-        engine.move_piece(1);
-        assert_eq!(engine.current_piece.col, start_col + 3);
-    }
+        // Cheese garbage is added below clean garbage.
+        let mut engine = BaseEngine::new();
+        engine.add_garbage_pattern(3, GarbagePattern::Clean(7));
+        engine.add_garbage_pattern(6, GarbagePattern::Cheese(1));
+        let cols = hole_cols(&engine, 9);
+        assert_eq!(&cols[6..], &[7, 7, 7]);
+        for pair in cols[..6].windows(2) {
+            assert_ne!(pair[0], pair[1]);
+        }
 
-    #[test]
-    fn test_engine_move_piece_collision() {
+        // The current piece is pushed up rather than overlapping the garbage.
         let mut engine = BaseEngine::new();
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        let row = engine.current_piece.row;
+        engine.add_garbage(2, 1);
+        assert!(!engine.has_collision());
+        assert_eq!(engine.current_piece.row, row + 2);
 
-        // Spawn new piece then move to far left.
-        engine.move_piece(-10);
-        // Moving further left should have no effect.
-        let far_left_col = engine.current_piece.col;
-        engine.move_piece(-1);
-        assert_eq!(engine.current_piece.col, far_left_col);
-        engine.move_piece(-1);
-        assert_eq!(engine.current_piece.col, far_left_col);
+        // Big pieces are pushed up by a multiple of their scale.
+        let mut engine = BaseEngine::new();
+        engine.set_big_mode(true);
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        let row = engine.current_piece.row;
+        engine.add_garbage(3, 1);
+        assert!(!engine.has_collision());
+        assert_eq!(engine.current_piece.row, row + 4);
 
-        // Spawn new piece then do same as above, but move right.
-        engine.next_piece();
-        engine.move_piece(10);
-        let far_right_col = engine.current_piece.col;
-        engine.move_piece(1);
-        assert_eq!(engine.current_piece.col, far_right_col);
-        engine.move_piece(1);
-        assert_eq!(engine.current_piece.col, far_right_col);
+        // Pieces falling upwards are also pushed up, away from the garbage.
+        let mut engine = BaseEngine::new();
+        engine.set_gravity_direction(GravityDirection::Up);
+        let row = engine.current_piece.row;
+        engine.add_garbage(1, 1);
+        assert!(!engine.has_collision());
+        assert_eq!(engine.current_piece.row, row + 1);
     }
 
     #[test]
-    fn test_engine_hold_piece() {
-        let mut engine = BaseEngine::new();
+    fn test_engine_snap() {
+        fn cols(engine: &BaseEngine) -> (i8, i8) {
+            let cols: Vec<i8> = engine
+                .current_piece
+                .get_cells()
+                .iter()
+                .map(|c| c.1)
+                .collect();
+            (*cols.iter().min().unwrap(), *cols.iter().max().unwrap())
+        }
 
-        assert!(engine.hold_piece.is_none());
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        assert_eq!(engine.snap_left(), 4);
+        assert_eq!(cols(&engine), (1, 2));
+        assert_eq!(engine.snap_left(), 0);
+        assert_eq!(engine.snap_right(), 8);
+        assert_eq!(cols(&engine), (9, 10));
+
+        // Snapping stops at blocks.
+        engine.playfield.set(21, 4);
+        engine.snap_left();
+        assert_eq!(cols(&engine), (5, 6));
+
+        // Snapping is also available as an input, which is only applied on initial press.
+        engine.input_snap_right();
+        engine.tick();
+        assert_eq!(cols(&engine), (9, 10));
+        engine.input_snap_left();
+        engine.tick();
+        assert_eq!(cols(&engine), (5, 6));
+        engine.playfield.clear(21, 4);
+        engine.input_snap_left();
+        engine.tick();
+        assert_eq!(cols(&engine), (5, 6));
+    }
 
-        let current_piece = engine.current_piece.piece.get_shape().clone();
-        engine.hold_piece();
+    #[test]
+    fn test_engine_receive_garbage() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        assert_eq!(engine.pending_garbage(), 0);
+
+        engine.receive_garbage(2, GarbagePattern::Clean(1));
+        engine.receive_garbage(0, GarbagePattern::Clean(1));
+        engine.receive_garbage(3, GarbagePattern::Cheese(0));
+        assert_eq!(engine.pending_garbage(), 5);
+        assert_eq!(engine.playfield.block_count(), 0);
+
+        // Garbage is added when the piece locks, below the piece.
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.pending_garbage(), 0);
+        assert_eq!(engine.playfield.block_count(), 5 * 9 + 4);
+        assert_eq!(
+            engine.playfield.get_tetromino(6, 5),
+            Option::Some(Tetromino::O)
+        );
+        // Garbage received first is added first, so it ends up on top.
+        assert_eq!(engine.playfield.get(5, 1), Space::Empty);
+        assert_eq!(engine.playfield.get(4, 1), Space::Empty);
 
-        let hold_piece = engine.hold_piece.unwrap();
-        assert_eq!(hold_piece, current_piece);
+        // Garbage is not added when the piece clears rows.
+        for col in 1..=Playfield::WIDTH {
+            if col != 5 && col != 6 {
+                engine.playfield.set(8, col);
+                engine.playfield.set(9, col);
+            }
+        }
+        engine.receive_garbage(1, GarbagePattern::Clean(1));
+        engine.tick();
+        engine.input_hard_drop();
+        engine.tick();
+        assert!(matches!(engine.state, State::LineClear(_)));
+        assert_eq!(engine.pending_garbage(), 1);
     }
 
     #[test]
-    fn test_engine_next_pieces() {
-        let mut engine = BaseEngine::new();
+    fn test_engine_garbage_timing() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.set_garbage_timing(GarbageTiming::Immediate);
+        engine.receive_garbage(2, GarbagePattern::Clean(1));
+        assert_eq!(engine.pending_garbage(), 0);
+        assert_eq!(engine.playfield.block_count(), 2 * 9);
 
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.set_garbage_timing(GarbageTiming::OnSpawn);
+        engine.receive_garbage(2, GarbagePattern::Clean(1));
         for _ in 0..10 {
-            let next_piece = engine.next_pieces[0];
-            engine.next_piece();
-            assert_eq!(engine.current_piece.piece.get_shape(), &next_piece);
+            engine.tick();
+        }
+        assert_eq!(engine.playfield.block_count(), 0);
+
+        // Garbage is not added when the piece locks, only when the next piece spawns.
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.state, State::Spawn);
+        assert_eq!(engine.pending_garbage(), 2);
+        assert_eq!(engine.playfield.block_count(), 4);
+        engine.tick();
+        assert_eq!(engine.pending_garbage(), 0);
+        assert_eq!(engine.playfield.block_count(), 2 * 9 + 4);
+        assert_eq!(
+            engine.playfield.get_tetromino(3, 5),
+            Option::Some(Tetromino::O)
+        );
+
+        // Garbage is added even if the previous piece cleared rows.
+        for col in 1..=Playfield::WIDTH {
+            if col != 5 && col != 6 {
+                engine.playfield.set(5, col);
+            }
         }
+        engine.receive_garbage(1, GarbagePattern::Clean(1));
+        engine.input_hard_drop();
+        engine.tick();
+        assert!(matches!(engine.state, State::LineClear(_)));
+        while engine.state != State::Spawn {
+            engine.tick();
+        }
+        assert_eq!(engine.pending_garbage(), 1);
+        engine.tick();
+        assert_eq!(engine.pending_garbage(), 0);
+
+        // Garbage is not added when a piece spawns from hold.
+        engine.receive_garbage(1, GarbagePattern::Clean(1));
+        engine.input_hold();
+        engine.tick();
+        assert_eq!(engine.state, State::Spawn);
+        engine.tick();
+        assert!(matches!(engine.state, State::Falling(_)));
+        assert_eq!(engine.pending_garbage(), 1);
+        engine.input_hard_drop();
+        engine.tick();
+        engine.tick();
+        assert_eq!(engine.pending_garbage(), 0);
     }
 
     #[test]
-    fn test_bag_generator() {
-        let bag_generator = BagGenerator::new();
+    fn test_engine_apply_attack() {
+        let mut engine = BaseEngine::new();
+        engine.receive_garbage(3, GarbagePattern::Clean(1));
+        assert_eq!(engine.apply_attack(5), 2);
+        assert_eq!(engine.pending_garbage(), 0);
+
+        engine.receive_garbage(5, GarbagePattern::Clean(1));
+        assert_eq!(engine.apply_attack(2), 0);
+        assert_eq!(engine.pending_garbage(), 3);
+
+        // Garbage received first is canceled first.
+        engine.receive_garbage(4, GarbagePattern::Clean(2));
+        assert_eq!(engine.apply_attack(4), 0);
+        assert_eq!(engine.pending_garbage(), 3);
+        assert_eq!(engine.pending_garbage[0], (3, GarbagePattern::Clean(2)));
+        assert_eq!(engine.apply_attack(0), 0);
+        assert_eq!(engine.pending_garbage(), 3);
+    }
 
-        // The bag generator should always generate tetrominos in sets containing one of each.
+    #[test]
+    fn test_engine_get_state() {
+        let mut engine = BaseEngine::new();
+        engine.set_gravity(Gravity::TicksPerRow(30));
+        assert_eq!(engine.get_state(), State::Falling(0));
+
+        for n in 1..=30 {
+            assert_eq!(engine.tick(), State::Falling(n));
+        }
+        // The piece drops and the count starts over.
+        let row = engine.current_piece.row;
+        assert_eq!(engine.tick(), State::Falling(1));
+        assert_eq!(engine.current_piece.row, row - 1);
+
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.get_state(), State::Spawn);
+        assert_eq!(engine.tick(), State::Falling(1));
+
+        assert_eq!(Gravity::TicksPerRow(4) * 2., Gravity::TicksPerRow(2));
+    }
+
+    #[test]
+    fn test_engine_holes_created_by_last_lock() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        assert_eq!(engine.holes_created_by_last_lock(), 0);
+
+        // The I piece bridges the gap at column 5.
+        for &col in [4, 6, 7].iter() {
+            engine.playfield.set(1, col);
+        }
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.holes_created_by_last_lock(), 1);
+        assert_eq!(engine.playfield.hole_count(), 1);
+
+        // Placing a piece flat on the stack does not create new holes.
+        engine.tick();
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.holes_created_by_last_lock(), 0);
+        assert_eq!(engine.playfield.hole_count(), 1);
+    }
+
+    #[test]
+    fn test_engine_lock_flash_grace() {
+        /// Returns an engine with the same setup as `test_engine_lock_after_floor_kick_lifts_piece`,
+        /// where the lock delay has just expired.
+        fn setup(grace: u32) -> BaseEngine {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+            engine.set_lock_flash_grace(grace);
+            engine.playfield.set(2, 4);
+            engine.playfield.set(2, 7);
+            engine.playfield.set(1, 6);
+            engine.current_piece.piece.rotate_cw();
+            engine.current_piece.row = 0;
+            engine.current_piece.col = 4;
+            for _ in 0..LOCK_DELAY {
+                engine.tick();
+            }
+            assert_eq!(engine.state, State::Lock(LOCK_DELAY));
+            engine
+        }
+
+        // Without a grace period, the piece locks instead of rotating.
+        let mut engine = setup(0);
+        engine.input_rotate_ccw();
+        engine.tick();
+        assert_eq!(engine.playfield.get(3, 5), Space::Block);
+
+        // Rotating within the grace period lifts the piece, delaying the lock.
+        let mut engine = setup(5);
+        engine.tick();
+        engine.input_rotate_ccw();
+        engine.tick();
+        assert_eq!(engine.state, State::Falling(1));
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        assert_eq!(engine.playfield.get(3, 5), Space::Empty);
+
+        // Rotating after the grace period is too late.
+        let mut engine = setup(5);
         for _ in 0..5 {
-            let mut tetrominos = HashSet::new();
-            for _ in 0..7 {
-                tetrominos.insert(bag_generator.next());
+            engine.tick();
+        }
+        assert_eq!(engine.state, State::Lock(LOCK_DELAY + 5));
+        engine.input_rotate_ccw();
+        engine.tick();
+        assert_eq!(engine.playfield.get(3, 5), Space::Block);
+
+        // Hold and drops are ignored during the grace period.
+        let mut engine = setup(5);
+        engine.input_hold();
+        engine.tick();
+        assert_eq!(engine.hold_piece, Option::None);
+        assert_eq!(engine.state, State::Lock(LOCK_DELAY + 1));
+    }
+
+    #[test]
+    fn test_engine_lock_reset_mode() {
+        /// Returns an engine whose O piece has been on the floor for 5 ticks.
+        fn setup(lock_reset_mode: LockResetMode) -> BaseEngine {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+            engine.set_lock_reset_mode(lock_reset_mode);
+            engine.set_gravity(Gravity::RowsPerTick(20));
+            while engine.state != State::Lock(1) {
+                engine.tick();
+            }
+            for _ in 0..5 {
+                engine.tick();
+            }
+            assert_eq!(engine.state, State::Lock(6));
+            engine
+        }
+
+        // Spinning in place resets the lock delay in move mode.
+        let mut engine = setup(LockResetMode::Move);
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(1));
+        engine.tick();
+        engine.input_move_right();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(1));
+
+        // Spinning and moving along the floor does not lower the piece, so the lock delay is not
+        // reset in step mode.
+        let mut engine = setup(LockResetMode::Step);
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(7));
+        engine.tick();
+        engine.input_move_right();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(9));
+
+        let mut engine = setup(LockResetMode::None);
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(7));
+
+        // In step mode, the piece still locks after the lock delay while spinning.
+        let mut engine = setup(LockResetMode::Step);
+        for n in 6..LOCK_DELAY {
+            if n % 2 == 0 {
+                engine.input_rotate_cw();
+            }
+            engine.tick();
+        }
+        assert_eq!(engine.state, State::Lock(LOCK_DELAY));
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.playfield.block_count(), 4);
+    }
+
+    #[test]
+    fn test_engine_gravity_disabled() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.set_gravity_enabled(false);
+        let row = engine.current_piece.row;
+        for _ in 0..1000 {
+            engine.tick();
+        }
+        assert_eq!(engine.current_piece.row, row);
+        assert!(matches!(engine.state, State::Falling(_)));
+
+        // Soft drop still moves the piece.
+        engine.input_soft_drop();
+        engine.tick();
+        assert!(engine.current_piece.row < row);
+
+        // Resting on the ground does not start the lock delay.
+        engine.current_piece = engine.ghost_piece();
+        for _ in 0..1000 {
+            engine.tick();
+        }
+        assert!(matches!(engine.state, State::Falling(_)));
+        assert_eq!(engine.playfield.block_count(), 0);
+
+        engine.input_hard_drop();
+        engine.tick();
+        assert_eq!(engine.playfield.block_count(), 4);
+
+        // Soft dropping onto the ground starts the lock delay.
+        engine.tick();
+        while engine.state != State::Lock(1) {
+            engine.input_soft_drop();
+            engine.tick();
+        }
+        for _ in 0..LOCK_DELAY {
+            engine.tick();
+        }
+        assert_eq!(engine.playfield.block_count(), 8);
+    }
+
+    #[test]
+    fn test_engine_rotation_buffer() {
+        /// Returns an engine with a horizontal I piece one row above the floor, where it cannot
+        /// rotate because of the block above it. It can rotate once it reaches the floor.
+        fn setup(buffer_ticks: u32) -> BaseEngine {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+            engine.set_rotation_buffer_ticks(buffer_ticks);
+            engine.set_gravity(Gravity::TicksPerRow(2));
+            engine.playfield.set(5, 7);
+            engine.current_piece.row = 0;
+            engine.state = State::Falling(1);
+            engine
+        }
+
+        // Without buffering, the rotation is lost.
+        let mut engine = setup(0);
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(1));
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+
+        // With buffering, the piece rotates as soon as it lands.
+        let mut engine = setup(1);
+        engine.input_rotate_cw();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        assert_eq!(engine.get_wasted_inputs(), 1);
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(1));
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::Clockwise
+        );
+        // The buffered rotation is counted as effective rather than wasted.
+        assert_eq!(engine.get_effective_inputs(), 1);
+        assert_eq!(engine.get_wasted_inputs(), 0);
+        for _ in 0..LOCK_DELAY {
+            engine.tick();
+        }
+        for row in 1..=4 {
+            assert_eq!(engine.playfield.get(row, 7), Space::Block);
+        }
+
+        // The buffered rotation expires.
+        let mut engine = setup(1);
+        engine.set_gravity(Gravity::TicksPerRow(3));
+        engine.input_rotate_cw();
+        engine.tick();
+        engine.tick();
+        engine.tick();
+        assert_eq!(engine.state, State::Lock(1));
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+    }
+
+    #[test]
+    fn test_engine_board_after_clear() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        for col in 1..=Playfield::WIDTH {
+            if !(4..=7).contains(&col) {
+                engine.playfield.set(1, col);
+                engine.playfield.set(3, col);
+            }
+        }
+        engine.playfield.set(2, 1);
+        engine.playfield.set_tetromino(4, 10, Tetromino::Z);
+
+        let preview = engine.board_after_clear(engine.ghost_piece());
+        // The playfield is unchanged.
+        assert_eq!(engine.playfield.block_count(), 6 * 2 + 2);
+
+        engine.input_hard_drop();
+        engine.tick();
+        while let State::LineClear(_) = engine.state {
+            engine.tick();
+        }
+        assert_eq!(preview.block_count(), 8);
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(preview.get(row, col), engine.playfield.get(row, col));
+                assert_eq!(
+                    preview.get_tetromino(row, col),
+                    engine.playfield.get_tetromino(row, col)
+                );
+            }
+        }
+        assert_eq!(preview.get_tetromino(3, 10), Option::Some(Tetromino::Z));
+    }
+
+    #[test]
+    fn test_engine_spawn_push_up() {
+        /// Returns an engine whose next piece is about to spawn where the specified function sets
+        /// blocks, given the cells of the piece.
+        fn setup(spawn_push_up: bool, block: fn(&mut Playfield, &[(i8, i8)])) -> BaseEngine {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+            engine.set_spawn_push_up(spawn_push_up);
+            engine.input_hard_drop();
+            engine.tick();
+            assert_eq!(engine.state, State::Spawn);
+            let cells = engine.current_piece.get_cells();
+            block(&mut engine.playfield, &cells);
+            engine
+        }
+
+        /// Blocks the lowest row of the spawn position.
+        fn block_lowest(playfield: &mut Playfield, cells: &[(i8, i8)]) {
+            let lowest = cells.iter().map(|&(row, _)| row).min().unwrap();
+            for &(row, col) in cells.iter().filter(|&&(row, _)| row == lowest) {
+                playfield.set(row as u8, col as u8);
+            }
+        }
+
+        /// Blocks the spawn position and the row above it.
+        fn block_all(playfield: &mut Playfield, cells: &[(i8, i8)]) {
+            for &(row, col) in cells {
+                playfield.set(row as u8, col as u8);
+                playfield.set(row as u8 + 1, col as u8);
+            }
+        }
+
+        let mut engine = setup(false, block_lowest);
+        assert_eq!(engine.tick(), State::TopOut);
+
+        // Pushing the piece up one row avoids the collision.
+        let mut engine = setup(true, block_lowest);
+        let row = engine.current_piece.row;
+        assert_eq!(engine.tick(), State::Falling(1));
+        assert_eq!(engine.current_piece.row, row + 1);
+
+        let mut engine = setup(true, block_all);
+        assert_eq!(engine.tick(), State::TopOut);
+    }
+
+    #[test]
+    fn test_engine_debug_clear_bottom_row() {
+        let mut engine = BaseEngine::new();
+        let observer = Rc::new(RecordingObserver::new());
+        engine.add_observer(observer.clone());
+
+        engine.playfield.set(1, 1);
+        engine.playfield.set(2, 2);
+        assert_eq!(engine.debug_clear_bottom_row(), 1);
+        assert_eq!(*observer.line_clears.borrow(), vec![1]);
+        // Rows above move down.
+        assert_eq!(engine.playfield.block_count(), 1);
+        assert_eq!(engine.playfield.get(1, 2), Space::Block);
+    }
+
+    #[test]
+    fn test_engine_tick_with_inputs() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        let col = engine.current_piece.col;
+        engine.tick_with_inputs(&actions(&[Action::MoveLeft]));
+        assert_eq!(engine.current_piece.col, col - 1);
+
+        // Inputs only apply to a single tick.
+        engine.tick_with_inputs(&HashSet::new());
+        engine.tick_with_inputs(&actions(&[Action::MoveRight, Action::RotateClockwise]));
+        assert_eq!(engine.current_piece.col, col);
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::Clockwise
+        );
+    }
+
+    #[test]
+    fn test_clear_rows() {
+        let mut engine = BaseEngine::new();
+
+        // Fill first, second, and fourth row.
+        for col in 1..=Playfield::WIDTH {
+            engine.playfield.set(1, col);
+            engine.playfield.set(2, col);
+            engine.playfield.set(4, col);
+        }
+        // Fill miscellaneous spaces in other rows.
+        engine.playfield.set(3, 3);
+        engine.playfield.set(3, 6);
+        engine.playfield.set(5, 1);
+        engine.playfield.set(6, 4);
+        engine.playfield.set(6, 10);
+        engine.playfield.set(7, 2);
+        engine.playfield.set(7, 5);
+        engine.playfield.set(7, 7);
+        engine.playfield.set(8, 9);
+
+        // Playfield should now look like this (ignoring empty rows).
+        // 8 --------#-
+        // 7 -#--#-#---
+        // 6 ---#-----#
+        // 5 #---------
+        // 4 ##########
+        // 3 --#--#----
+        // 2 ##########
+        // 1 ##########
+        //   1234567890
+
+        engine.clear_rows();
+        // Playfield should now look like this (ignoring empty rows).
+        // 5 --------#-
+        // 4 -#--#-#---
+        // 3 ---#-----#
+        // 2 #---------
+        // 1 --#--#----
+        //   1234567890
+        assert_eq!(engine.playfield.get(1, 3), Space::Block);
+        assert_eq!(engine.playfield.get(1, 6), Space::Block);
+        assert_eq!(engine.playfield.get(2, 1), Space::Block);
+        assert_eq!(engine.playfield.get(3, 4), Space::Block);
+        assert_eq!(engine.playfield.get(3, 10), Space::Block);
+        assert_eq!(engine.playfield.get(4, 2), Space::Block);
+        assert_eq!(engine.playfield.get(4, 5), Space::Block);
+        assert_eq!(engine.playfield.get(4, 7), Space::Block);
+        assert_eq!(engine.playfield.get(5, 9), Space::Block);
+
+        // Rows above should be empty.
+        for row in 6..=8 {
+            for col in 1..Playfield::WIDTH {
+                assert_eq!(engine.playfield.get(row, col), Space::Empty);
             }
-            assert_eq!(tetrominos.len(), 7);
         }
     }
+
+    #[test]
+    fn test_clear_rows_preserves_order() {
+        let mut engine = BaseEngine::new();
+
+        // Fill rows 2, 5, and 8. Each of the other 7 rows up to 10 gets a unique pattern: a single
+        // block in the column matching its row, placed by a different tetromino.
+        let shapes = Tetromino::ALL;
+        let full_rows = [2, 5, 8];
+        let partial_rows: Vec<u8> = (1..=10).filter(|row| !full_rows.contains(row)).collect();
+        for row in full_rows.iter() {
+            for col in 1..=Playfield::WIDTH {
+                engine.playfield.set(*row, col);
+            }
+        }
+        for (i, row) in partial_rows.iter().enumerate() {
+            engine.playfield.set_tetromino(*row, *row, shapes[i]);
+        }
+
+        assert_eq!(engine.clear_rows(), 3);
+
+        // Surviving rows move down in their original order.
+        for (i, old_row) in partial_rows.iter().enumerate() {
+            let row = i as u8 + 1;
+            for col in 1..=Playfield::WIDTH {
+                if col == *old_row {
+                    assert_eq!(
+                        engine.playfield.get_tetromino(row, col),
+                        Option::Some(shapes[i])
+                    );
+                }
+                else {
+                    assert_eq!(engine.playfield.get(row, col), Space::Empty);
+                }
+            }
+        }
+        assert_eq!(engine.playfield.highest_block_row(), Option::Some(7));
+    }
+
+    #[test]
+    fn test_engine_rotate_piece() {
+        let mut engine = BaseEngine::new();
+
+        // Rotate clockwise.
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        engine.rotate_piece_cw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::Clockwise
+        );
+        engine.rotate_piece_cw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::OneEighty
+        );
+        engine.rotate_piece_cw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::CounterClockwise
+        );
+        engine.rotate_piece_cw();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+
+        // Rotate counter-clockwise.
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        engine.rotate_piece_ccw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::CounterClockwise
+        );
+        engine.rotate_piece_ccw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::OneEighty
+        );
+        engine.rotate_piece_ccw();
+        assert_eq!(
+            engine.current_piece.piece.get_rotation(),
+            &Rotation::Clockwise
+        );
+        engine.rotate_piece_ccw();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+    }
+
+    #[test]
+    fn test_engine_rotate_piece_collision() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        engine.next_piece();
+
+        // Surround above and below to prevent rotation.
+        for col in 4..=7 {
+            engine.playfield.set(20, col);
+            engine.playfield.set(22, col);
+        }
+
+        // attempt rotate
+        engine.rotate_piece_cw();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+
+        engine.rotate_piece_ccw();
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+    }
+
+    #[test]
+    fn test_engine_rotate_piece_wall_kick() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.next_piece();
+
+        // Setup wall kick
+        // T---------
+        // TT#-------
+        // T--#------
+        engine.playfield.set(1, 4);
+        engine.playfield.set(2, 3);
+        engine.rotate_piece_cw();
+        engine.move_piece(-10);
+        engine.drop(Playfield::VISIBLE_HEIGHT);
+
+        // Perform wall kick and lock into place.
+        // ----------
+        // -T#-------
+        // TTT#------
+        engine.rotate_piece_ccw();
+        engine.lock();
+
+        // Check that piece in expected position.
+        assert_eq!(engine.playfield.get(1, 1), Space::Block);
+        assert_eq!(engine.playfield.get(1, 2), Space::Block);
+        assert_eq!(engine.playfield.get(1, 3), Space::Block);
+        assert_eq!(engine.playfield.get(2, 2), Space::Block);
+    }
+
+    #[test]
+    fn test_engine_reachable_placements() {
+        let generators = vec![
+            (SingleTetrominoGenerator::I, 17),
+            (SingleTetrominoGenerator::O, 9),
+            (SingleTetrominoGenerator::T, 34),
+            (SingleTetrominoGenerator::S, 17),
+            (SingleTetrominoGenerator::Z, 17),
+            (SingleTetrominoGenerator::J, 34),
+            (SingleTetrominoGenerator::L, 34),
+        ];
+        for (generator, expected) in generators {
+            let mut engine = BaseEngine::with_tetromino_generator(Box::new(generator));
+            let spawn = engine.current_piece;
+
+            // On an empty board, every column and rotation is reachable.
+            let placements = engine.reachable_placements();
+            assert_eq!(placements.len(), expected);
+
+            // Each path leads to its placement.
+            for (placement, path) in placements {
+                engine.current_piece = spawn;
+                for action in path {
+                    match action {
+                        Action::MoveLeft => assert_eq!(engine.move_piece(-1), 1),
+                        Action::MoveRight => assert_eq!(engine.move_piece(1), 1),
+                        Action::RotateClockwise => assert!(engine.rotate_piece_cw()),
+                        Action::RotateCounterClockwise => assert!(engine.rotate_piece_ccw()),
+                        Action::SoftDrop => assert_eq!(engine.drop_one(), 1),
+                        _ => panic!("Unexpected action {:?}.", action),
+                    }
+                }
+                assert_eq!(engine.current_piece, placement);
+                assert!(engine.is_in_lock_position());
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_piece_fits_at() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.next_piece();
+        // ----------
+        // #-#-------
+        // #-##------
+        for &(row, col) in [(1, 1), (1, 3), (1, 4), (2, 1), (2, 3)].iter() {
+            engine.playfield.set(row, col);
+        }
+
+        // The current position is not changed.
+        let piece = engine.current_piece;
+        // T pointing down into the gap.
+        assert!(engine.piece_fits_at(1, 1, Rotation::OneEighty));
+        assert!(!engine.piece_fits_at(0, 1, Rotation::OneEighty));
+        // T pointing up overlaps the blocks on either side.
+        assert!(!engine.piece_fits_at(0, 1, Rotation::Spawn));
+        // T pointing right overlaps the block on the right unless it is above it.
+        assert!(engine.piece_fits_at(1, 1, Rotation::Clockwise));
+        assert!(!engine.piece_fits_at(0, 1, Rotation::Clockwise));
+        // Out of bounds.
+        assert!(!engine.piece_fits_at(1, -1, Rotation::Spawn));
+        assert_eq!(engine.current_piece, piece);
+    }
+
+    #[test]
+    fn test_engine_is_in_danger() {
+        let mut engine = BaseEngine::new();
+        assert!(!engine.is_in_danger());
+
+        // Rows 17 to 20 are dangerous by default.
+        for row in 1..=16 {
+            engine.playfield.set(row, 1);
+            assert!(!engine.is_in_danger());
+        }
+        engine.playfield.set(17, 1);
+        assert!(engine.is_in_danger());
+
+        engine.set_danger_threshold(2);
+        assert!(!engine.is_in_danger());
+        engine.playfield.set(19, 1);
+        assert!(engine.is_in_danger());
+
+        // Large thresholds do not overflow.
+        engine.set_danger_threshold(u8::MAX);
+        assert!(engine.is_in_danger());
+        engine.playfield.set(Playfield::TOTAL_HEIGHT, 1);
+        assert!(engine.is_in_danger());
+    }
+
+    #[test]
+    fn test_engine_pieces_until() {
+        let engine = BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(&[
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::I,
+            Tetromino::Z,
+            Tetromino::O,
+            Tetromino::Z,
+        ])));
+        // Next queue is T, S, I, Z, O.
+        assert_eq!(engine.pieces_until(Tetromino::T), Option::Some(0));
+        assert_eq!(engine.pieces_until(Tetromino::I), Option::Some(2));
+        assert_eq!(engine.pieces_until(Tetromino::Z), Option::Some(3));
+        assert_eq!(engine.pieces_until(Tetromino::O), Option::Some(4));
+        assert_eq!(engine.pieces_until(Tetromino::L), Option::None);
+    }
+
+    #[test]
+    fn test_engine_entry_delay() {
+        let mut engine = BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(&[
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+        ])));
+        engine.set_entry_delay(3);
+        engine.tick();
+
+        engine.input_hard_drop();
+        assert!(matches!(engine.tick(), State::Are(1)));
+        assert!(matches!(engine.tick(), State::Are(2)));
+        assert!(matches!(engine.tick(), State::Are(3)));
+        assert!(matches!(engine.tick(), State::Spawn));
+        assert!(matches!(engine.tick(), State::Falling(1)));
+        assert_eq!(engine.current_piece.piece.get_shape(), &Tetromino::O);
+        assert_eq!(engine.hold_piece, Option::None);
+    }
+
+    #[test]
+    fn test_engine_initial_hold() {
+        let mut engine = BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(&[
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+        ])));
+        engine.set_entry_delay(3);
+        engine.hold_piece = Option::Some(Tetromino::L);
+        engine.tick();
+
+        engine.input_hard_drop();
+        engine.tick();
+        // Hold the hold input through the entry delay.
+        while let State::Are(_) = engine.state {
+            engine.input_hold();
+            engine.tick();
+        }
+
+        // The hold piece spawns in place of the next piece.
+        assert!(matches!(engine.state, State::Spawn));
+        assert_eq!(engine.current_piece.piece.get_shape(), &Tetromino::L);
+        assert_eq!(engine.hold_piece, Option::Some(Tetromino::O));
+        assert!(!engine.is_hold_available);
+
+        // Continuing to hold does not swap the pieces again.
+        engine.input_hold();
+        assert!(matches!(engine.tick(), State::Falling(1)));
+        engine.input_hold();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_shape(), &Tetromino::L);
+    }
+
+    #[test]
+    fn test_engine_spawn_policy() {
+        fn lowest_row(engine: &BaseEngine) -> i8 {
+            engine.current_piece.lowest_occupied_row()
+        }
+
+        let mut engine = BaseEngine::new();
+        for _ in 0..10 {
+            // Pieces are above the visible playfield until they drop.
+            let spawn_row = engine.current_piece.row;
+            while engine.current_piece.row == spawn_row {
+                assert_eq!(lowest_row(&engine), 21);
+                engine.tick();
+            }
+            assert_eq!(lowest_row(&engine), 20);
+            engine.next_piece();
+        }
+
+        engine.set_spawn_policy(SpawnPolicy::VisibleTop);
+        assert_eq!(lowest_row(&engine), 20);
+        for _ in 0..10 {
+            engine.next_piece();
+            assert_eq!(lowest_row(&engine), 20);
+        }
+    }
+
+    #[test]
+    fn test_engine_is_topped_out() {
+        let mut engine = BaseEngine::new();
+        assert!(!engine.is_topped_out());
+
+        // Pieces spawn above the visible playfield by default.
+        for col in 4..=7 {
+            engine.playfield.set(20, col);
+        }
+        assert!(!engine.playfield.is_topped_out());
+        assert!(!engine.is_topped_out());
+
+        engine.set_spawn_policy(SpawnPolicy::VisibleTop);
+        assert!(engine.is_topped_out());
+
+        // Every piece can be pushed up into a free position.
+        engine.set_spawn_push_up(true);
+        assert!(!engine.is_topped_out());
+    }
+
+    #[test]
+    fn test_engine_big_mode() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.set_big_mode(true);
+
+        // Each block is a 2 x 2 square, and the piece is still above the visible playfield.
+        let cells = engine.current_piece.get_cells();
+        assert_eq!(cells.len(), 16);
+        assert_eq!(cells.iter().map(|(row, _)| *row).min(), Option::Some(21));
+        assert_eq!(cells.iter().map(|(_, col)| *col).min(), Option::Some(3));
+
+        // A single move shifts the piece by two columns.
+        let col = engine.current_piece.col;
+        assert_eq!(engine.move_piece(-1), 1);
+        assert_eq!(engine.current_piece.col, col - 2);
+        assert_eq!(engine.move_piece(-1), 0);
+
+        // Drops are two rows at a time.
+        let row = engine.current_piece.row;
+        assert_eq!(engine.drop_one(), 1);
+        assert_eq!(engine.current_piece.row, row - 2);
+
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        assert!(engine.is_in_lock_position());
+        engine.lock();
+        assert_eq!(engine.playfield.block_count(), 16);
+        for row in 1..=4 {
+            for col in 1..=4 {
+                assert_eq!(engine.playfield.get(row, col), Space::Block);
+            }
+        }
+
+        // The next piece fits against the right wall.
+        engine.next_piece();
+        engine.move_piece(10);
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        engine.lock();
+        assert_eq!(engine.playfield.block_count(), 32);
+        assert_eq!(engine.playfield.get(4, 10), Space::Block);
+        assert_eq!(engine.playfield.highest_block_row(), Option::Some(4));
+    }
+
+    #[test]
+    fn test_engine_try_rotate() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.next_piece();
+
+        // Rotating back to spawn rotation against the left wall requires a kick to the right.
+        // |#--       |-#-
+        // |##-   ->  |###
+        // |#--       |---
+        assert!(engine.try_rotate_cw());
+        engine.move_piece(-10);
+        assert_eq!(engine.current_piece.col, 0);
+        assert!(engine.try_rotate_ccw());
+        assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+        assert_eq!(engine.current_piece.col, 1);
+        assert!(engine.last_action_was_rotation);
+
+        // Fill every space except those occupied by the piece, so that no rotation is possible.
+        let cells = engine.current_piece.get_cells();
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                if !cells.contains(&(row as i8, col as i8)) {
+                    engine.playfield.set(row, col);
+                }
+            }
+        }
+        let piece = engine.current_piece;
+        assert!(!engine.try_rotate_cw());
+        assert!(!engine.try_rotate_ccw());
+        assert_eq!(engine.current_piece, piece);
+    }
+
+    #[test]
+    fn test_engine_action_order() {
+        fn apply_move_left_and_rotate(action_order: ActionOrder) -> CurrentPiece {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+            engine.next_piece();
+            engine.set_action_order(action_order);
+            engine.move_piece(-10);
+            assert_eq!(engine.current_piece.col, 1);
+
+            engine.apply_actions(&actions(&[Action::MoveLeft, Action::RotateClockwise]));
+            engine.current_piece
+        }
+
+        // Piece is against the wall, so it can not move until after it is rotated.
+        // #--       -#-       #--
+        // ##-   ->  -##  or   ##-
+        // #--       -#-       #--
+        let piece = apply_move_left_and_rotate(ActionOrder::MoveFirst);
+        assert_eq!(piece.piece.get_rotation(), &Rotation::Clockwise);
+        assert_eq!(piece.col, 1);
+
+        let piece = apply_move_left_and_rotate(ActionOrder::RotateFirst);
+        assert_eq!(piece.piece.get_rotation(), &Rotation::Clockwise);
+        assert_eq!(piece.col, 0);
+    }
+
+    #[test]
+    fn test_engine_move_piece() {
+        let mut engine = BaseEngine::new();
+
+        // Test move left.
+        let start_col = engine.current_piece.col;
+        engine.move_piece(-1);
+        assert_eq!(engine.current_piece.col, start_col - 1);
+        engine.move_piece(-1);
+        assert_eq!(engine.current_piece.col, start_col - 2);
+        engine.move_piece(-1);
+        assert_eq!(engine.current_piece.col, start_col - 3);
+
+        // Spawn a new piece then test move right.
+        let start_col = engine.current_piece.col;
+        engine.move_piece(1);
+        assert_eq!(engine.current_piece.col, start_col + 1);
+        engine.move_piece(1);
+        assert_eq!(engine.current_piece.col, start_col + 2);
+        engine.move_piece(1);
+        assert_eq!(engine.current_piece.col, start_col + 3);
+    }
+
+    #[test]
+    fn test_engine_move_piece_collision() {
+        let mut engine = BaseEngine::new();
+
+        // Spawn new piece then move to far left.
+        engine.move_piece(-10);
+        // Moving further left should have no effect.
+        let far_left_col = engine.current_piece.col;
+        engine.move_piece(-1);
+        assert_eq!(engine.current_piece.col, far_left_col);
+        engine.move_piece(-1);
+        assert_eq!(engine.current_piece.col, far_left_col);
+
+        // Spawn new piece then do same as above, but move right.
+        engine.next_piece();
+        engine.move_piece(10);
+        let far_right_col = engine.current_piece.col;
+        engine.move_piece(1);
+        assert_eq!(engine.current_piece.col, far_right_col);
+        engine.move_piece(1);
+        assert_eq!(engine.current_piece.col, far_right_col);
+    }
+
+    #[test]
+    fn test_engine_hold_piece() {
+        let mut engine = BaseEngine::new();
+
+        assert!(engine.hold_piece.is_none());
+
+        let current_piece = engine.current_piece.piece.get_shape().clone();
+        engine.hold_piece();
+
+        let hold_piece = engine.hold_piece.unwrap();
+        assert_eq!(hold_piece, current_piece);
+    }
+
+    #[test]
+    fn test_engine_first_hold_next_pieces() {
+        let mut engine = BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(&[
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::Z,
+            Tetromino::J,
+            Tetromino::L,
+        ])));
+        assert_eq!(
+            engine.get_next_pieces(),
+            vec![
+                Tetromino::O,
+                Tetromino::T,
+                Tetromino::S,
+                Tetromino::Z,
+                Tetromino::J
+            ]
+        );
+
+        // Holding with an empty hold slot takes exactly one piece from the queue.
+        engine.input_hold();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_shape(), &Tetromino::O);
+        assert_eq!(
+            engine.get_next_pieces(),
+            vec![
+                Tetromino::T,
+                Tetromino::S,
+                Tetromino::Z,
+                Tetromino::J,
+                Tetromino::L
+            ]
+        );
+
+        // Swapping with the hold piece does not take any pieces from the queue.
+        engine.tick();
+        engine.tick();
+        engine.input_hard_drop();
+        engine.tick();
+        engine.tick();
+        engine.input_hold();
+        engine.tick();
+        assert_eq!(engine.current_piece.piece.get_shape(), &Tetromino::I);
+        assert_eq!(engine.hold_piece, Option::Some(Tetromino::T));
+        assert_eq!(
+            engine.get_next_pieces(),
+            vec![
+                Tetromino::S,
+                Tetromino::Z,
+                Tetromino::J,
+                Tetromino::L,
+                Tetromino::I
+            ]
+        );
+    }
+
+    #[test]
+    fn test_engine_hold_piece_notifies_observers() {
+        let mut engine = BaseEngine::new();
+        let observer = Rc::new(RecordingObserver::new());
+        engine.add_observer(observer.clone());
+
+        let first = *engine.current_piece.piece.get_shape();
+        let next = engine.get_next_pieces()[0];
+
+        // First hold swaps in the next piece.
+        engine.hold_piece();
+        assert_eq!(*observer.holds.borrow(), vec![(first, next)]);
+
+        // Second hold swaps back the first piece.
+        engine.hold_piece();
+        assert_eq!(*observer.holds.borrow(), vec![(first, next), (next, first)]);
+    }
+
+    #[test]
+    fn test_engine_hold_into_blocked_spawn() {
+        let mut engine = BaseEngine::new();
+        engine.tick();
+
+        // Block the spawn position.
+        for col in 1..=Playfield::WIDTH {
+            engine.playfield.set(21, col);
+            engine.playfield.set(22, col);
+        }
+
+        engine.input_hold();
+        assert!(matches!(engine.tick(), State::Spawn));
+        assert!(matches!(engine.tick(), State::TopOut));
+    }
+
+    #[test]
+    fn test_engine_next_pieces() {
+        let mut engine = BaseEngine::new();
+
+        for _ in 0..10 {
+            let next_piece = engine.get_next_pieces()[0];
+            engine.next_piece();
+            assert_eq!(engine.current_piece.piece.get_shape(), &next_piece);
+        }
+    }
+
+    #[test]
+    fn test_engine_next_pieces_iter() {
+        let mut engine = BaseEngine::new();
+        for preview_count in 0..=PREVIEW_COUNT {
+            engine.set_preview_count(preview_count);
+            let pieces: Vec<Tetromino> = engine.next_pieces_iter().collect();
+            assert_eq!(pieces, engine.get_next_pieces());
+            assert_eq!(pieces.len(), preview_count);
+            engine.next_piece();
+        }
+    }
+
+    #[test]
+    fn test_engine_preview_count() {
+        let sequence = [
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::Z,
+            Tetromino::J,
+            Tetromino::L,
+        ];
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SequenceGenerator::new(&sequence)));
+        assert_eq!(engine.get_next_pieces().len(), 5);
+
+        // Reducing the preview count hides pieces without skipping them.
+        engine.set_preview_count(0);
+        assert!(engine.get_next_pieces().is_empty());
+        assert_eq!(engine.pieces_until(Tetromino::O), Option::None);
+        for i in 1..=sequence.len() * 2 {
+            engine.next_piece();
+            assert_eq!(
+                engine.current_piece.piece.get_shape(),
+                &sequence[i % sequence.len()]
+            );
+        }
+
+        engine.set_preview_count(3);
+        assert_eq!(
+            engine.get_next_pieces(),
+            vec![Tetromino::O, Tetromino::T, Tetromino::S]
+        );
+    }
+
+    #[test]
+    fn test_seeded_bag_generator() {
+        fn generate(generator: &dyn TetrominoGenerator, n: usize) -> Vec<Tetromino> {
+            (0..n).map(|_| generator.next()).collect()
+        }
+
+        let generator = SeededBagGenerator::new(1);
+        let first = generate(&generator, 10);
+        assert_eq!(first, generate(&SeededBagGenerator::new(1), 10));
+
+        // Reseeding in the middle of a bag starts a new bag from the new seed.
+        generator.reseed(2);
+        assert_eq!(
+            generate(&generator, 14),
+            generate(&SeededBagGenerator::new(2), 14)
+        );
+        generator.reseed(1);
+        assert_eq!(generate(&generator, 10), first);
+    }
+
+    #[test]
+    fn test_engine_reseed_generator() {
+        let mut engine = BaseEngine::with_seed(1);
+        let queue = engine.get_next_pieces();
+        assert!(engine.reseed_generator(2));
+
+        // Pieces in the queue are unchanged. Pieces after the queue follow the new seed.
+        let generator = SeededBagGenerator::new(2);
+        let expected = queue.into_iter().chain((0..15).map(|_| generator.next()));
+        for shape in expected {
+            engine.next_piece();
+            assert_eq!(engine.current_piece.piece.get_shape(), &shape);
+        }
+
+        // The queue is the same whether or not it was used before reseeding.
+        let engine = BaseEngine::with_seed(1);
+        assert!(engine.reseed_generator(2));
+        assert_eq!(
+            engine.get_next_pieces(),
+            BaseEngine::with_seed(1).get_next_pieces()
+        );
+
+        // The default number of pieces are generated from the old seed, even with a shorter preview.
+        let mut engine = BaseEngine::with_seed(1);
+        engine.set_preview_count(2);
+        assert!(engine.reseed_generator(2));
+        engine.set_preview_count(PREVIEW_COUNT);
+        assert_eq!(
+            engine.get_next_pieces(),
+            BaseEngine::with_seed(1).get_next_pieces()
+        );
+
+        // Unseeded generators can not be reseeded.
+        assert!(!BaseEngine::new().reseed_generator(2));
+    }
+
+    #[test]
+    fn test_bag_generator() {
+        let bag_generator = BagGenerator::new();
+
+        // The bag generator should always generate tetrominos in sets containing one of each.
+        for _ in 0..5 {
+            let mut tetrominos = HashSet::new();
+            for _ in 0..7 {
+                tetrominos.insert(bag_generator.next());
+            }
+            assert_eq!(tetrominos.len(), 7);
+        }
+    }
+
+    #[test]
+    fn test_bag_generator_boundaries() {
+        fn assert_bags(generator: &dyn TetrominoGenerator) {
+            // Partially consume the first bag. The rest of the bag contains the remaining pieces.
+            let mut tetrominos: HashSet<Tetromino> = (0..3).map(|_| generator.next()).collect();
+            assert_eq!(tetrominos.len(), 3);
+            for _ in 0..4 {
+                assert!(tetrominos.insert(generator.next()));
+            }
+
+            // Every following bag contains one of each.
+            for _ in 0..10 {
+                let bag: HashSet<Tetromino> = (0..7).map(|_| generator.next()).collect();
+                assert_eq!(bag.len(), 7);
+            }
+        }
+
+        assert_bags(&BagGenerator::new());
+        assert_bags(&SeededBagGenerator::new(0));
+        assert_bags(&ValidatingGenerator {
+            generator: Box::new(BagGenerator::new()),
+        });
+
+        // The engine consumes part of the first bag to fill the queue.
+        let mut engine = BaseEngine::with_seed(0);
+        let mut shapes = vec![];
+        for _ in 0..21 {
+            shapes.push(*engine.current_piece.piece.get_shape());
+            engine.next_piece();
+        }
+        for bag in shapes.chunks(7) {
+            assert_eq!(bag.iter().collect::<HashSet<_>>().len(), 7);
+        }
+    }
+
+    #[test]
+    fn test_engine_t_spin_requires_rotation_last() {
+        // Setup mini t-spin, rotating clockwise from counter-clockwise rotation.
+        // ----------
+        // --#T------
+        // --TT------
+        // --#T#-----
+        fn setup() -> (BaseEngine, Rc<RecordingObserver>) {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+            let observer = Rc::new(RecordingObserver::new());
+            engine.add_observer(observer.clone());
+            engine.playfield.set(1, 3);
+            engine.playfield.set(1, 5);
+            engine.playfield.set(3, 3);
+            engine.current_piece.rotate_ccw();
+            engine.current_piece.row = 0;
+            engine.current_piece.col = 3;
+
+            engine.apply_actions(&actions(&[Action::RotateClockwise]));
+            assert_eq!(engine.current_piece.piece.get_rotation(), &Rotation::Spawn);
+            (engine, observer)
+        }
+
+        // Locking immediately after the rotation is a t-spin.
+        let (mut engine, observer) = setup();
+        assert!(engine.last_action_was_rotation);
+        engine.apply_lock();
+        assert_eq!(*observer.locks.borrow(), vec![TSpin::Mini]);
+
+        // Moving after the rotation is not a t-spin.
+        let (mut engine, observer) = setup();
+        engine.apply_actions(&actions(&[Action::MoveRight]));
+        assert_eq!(engine.current_piece.col, 4);
+        assert!(!engine.last_action_was_rotation);
+        engine.apply_lock();
+        assert_eq!(*observer.locks.borrow(), vec![TSpin::None]);
+    }
+
+    #[test]
+    fn test_engine_t_spin_clears_are_distinguishable() {
+        use std::cell::Cell;
+
+        /// Records each line clear along with the T-spin of the piece which cleared it.
+        struct ClearObserver {
+            last_lock: Cell<TSpin>,
+            clears: RefCell<Vec<(TSpin, u8)>>,
+        }
+
+        impl BaseEngineObserver for ClearObserver {
+            fn on_lock(&self, t_spin: TSpin, _playfield: &Playfield) {
+                self.last_lock.set(t_spin);
+            }
+
+            fn on_line_clear(&self, n_rows: u8) {
+                self.clears
+                    .borrow_mut()
+                    .push((self.last_lock.get(), n_rows));
+            }
+        }
+
+        /// Rotates the current piece clockwise, fills the rest of the row of the lowest block of
+        /// the piece, then locks it and clears the row.
+        fn rotate_and_clear(mut engine: BaseEngine) -> Vec<(TSpin, u8)> {
+            let observer = Rc::new(ClearObserver {
+                last_lock: Cell::new(TSpin::None),
+                clears: RefCell::new(vec![]),
+            });
+            engine.add_observer(observer.clone());
+
+            assert!(engine.try_rotate_cw());
+            let cells = engine.current_piece.get_cells();
+            let row = cells.iter().map(|&(row, _)| row).min().unwrap();
+            for col in 1..=Playfield::WIDTH as i8 {
+                if !cells.contains(&(row, col)) {
+                    engine.playfield.set(row as u8, col as u8);
+                }
+            }
+
+            engine.apply_lock();
+            while let State::LineClear(_) = engine.state {
+                engine.tick();
+            }
+            let clears = observer.clears.borrow().clone();
+            clears
+        }
+
+        // Mini T-spin, as in `test_engine_t_spin_requires_rotation_last`.
+        // ----------
+        // --#T------
+        // --TT------
+        // --#T#-----
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.playfield.set(1, 3);
+        engine.playfield.set(1, 5);
+        engine.playfield.set(3, 3);
+        engine.current_piece.rotate_ccw();
+        engine.current_piece.row = 0;
+        engine.current_piece.col = 3;
+        assert_eq!(rotate_and_clear(engine), vec![(TSpin::Mini, 1)]);
+
+        // Regular T-spin, rotating into an upside down T under an overhang.
+        // ---#------
+        // ---TTT----
+        // ---#T#----
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.playfield.set(3, 4);
+        engine.playfield.set(1, 4);
+        engine.playfield.set(1, 6);
+        engine.current_piece.rotate_cw();
+        engine.current_piece.row = 0;
+        engine.current_piece.col = 4;
+        assert_eq!(rotate_and_clear(engine), vec![(TSpin::Regular, 1)]);
+    }
+
+    #[test]
+    fn test_engine_would_be_tspin() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        let observer = Rc::new(RecordingObserver::new());
+        engine.add_observer(observer.clone());
+        assert_eq!(engine.would_be_tspin(RotateDir::Clockwise), TSpin::None);
+        assert_eq!(
+            engine.would_be_tspin(RotateDir::CounterClockwise),
+            TSpin::None
+        );
+
+        // T-spin double slot, with a clockwise T above it.
+        // ---#------
+        // ###T--####
+        // ####T#####
+        engine.playfield.set(3, 4);
+        for col in 1..=Playfield::WIDTH {
+            if !(4..=6).contains(&col) {
+                engine.playfield.set(2, col);
+            }
+            if col != 5 {
+                engine.playfield.set(1, col);
+            }
+        }
+        engine.current_piece.rotate_cw();
+        engine.current_piece.row = 0;
+        engine.current_piece.col = 4;
+        let piece = engine.current_piece;
+
+        assert_eq!(engine.would_be_tspin(RotateDir::Clockwise), TSpin::Regular);
+        assert_eq!(engine.current_piece, piece);
+        assert_eq!(engine.current_t_spin, TSpinInternal::None);
+
+        // The prediction matches the actual rotation.
+        assert!(engine.try_rotate_cw());
+        engine.apply_lock();
+        assert_eq!(*observer.locks.borrow(), vec![TSpin::Regular]);
+    }
+
+    #[test]
+    fn test_engine_set_gravity_zero_ticks_per_row() {
+        let mut engine = BaseEngine::new();
+        engine.set_gravity(Gravity::TicksPerRow(0));
+        let start_row = engine.current_piece.row;
+
+        // The first tick starts the falling counter.
+        engine.tick();
+        assert_eq!(engine.current_piece.row, start_row);
+
+        // The piece should then drop one row every tick.
+        for drop in 1..=5 {
+            engine.tick();
+            assert_eq!(engine.current_piece.row, start_row - drop);
+        }
+    }
+
+    #[test]
+    fn test_action_set() {
+        // Every combination of actions behaves the same as a `HashSet`.
+        for mask in 0..1u32 << ALL_ACTIONS.len() {
+            let subset: Vec<Action> = ALL_ACTIONS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & 1 << i != 0)
+                .map(|(_, action)| *action)
+                .collect();
+            let mut hash_set: HashSet<Action> = actions(&subset);
+            let mut action_set: ActionSet = actions(&subset);
+
+            assert_eq!(action_set.is_empty(), hash_set.is_empty());
+            assert_eq!(action_set.iter().count(), hash_set.len());
+            for action in ALL_ACTIONS.iter() {
+                assert_eq!(action_set.contains(action), hash_set.contains(action));
+            }
+            assert_eq!(
+                action_set.iter().cloned().collect::<HashSet<Action>>(),
+                hash_set
+            );
+
+            for action in ALL_ACTIONS.iter() {
+                let mut action_set = action_set;
+                let mut hash_set = hash_set.clone();
+                assert_eq!(action_set.insert(*action), hash_set.insert(*action));
+                assert_eq!(action_set.insert(*action), hash_set.insert(*action));
+                assert_eq!(action_set.iter().count(), hash_set.len());
+            }
+            for action in ALL_ACTIONS.iter() {
+                assert_eq!(action_set.remove(action), hash_set.remove(action));
+                assert_eq!(action_set.remove(action), hash_set.remove(action));
+                assert_eq!(action_set.iter().count(), hash_set.len());
+            }
+            assert!(action_set.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_engine_process_input_no_input() {
+        let mut engine = BaseEngine::new();
+
+        // No input.
+        assert!(engine.process_input().is_empty());
+        assert!(engine
+            .current_inputs
+            .values()
+            .all(|duration| *duration == 0));
+
+        // Hold left for a few ticks.
+        for duration in 1..=3 {
+            engine.input_move_left();
+            let actions = engine.process_input();
+            assert_eq!(actions.contains(&Action::MoveLeft), duration == 1);
+            assert_eq!(engine.current_inputs[&Action::MoveLeft], duration);
+        }
+
+        // Releasing left must still reset its duration.
+        assert!(engine.process_input().is_empty());
+        assert_eq!(engine.current_inputs[&Action::MoveLeft], 0);
+
+        // So that pressing again is treated as an initial press.
+        engine.input_move_left();
+        assert!(engine.process_input().contains(&Action::MoveLeft));
+    }
+
+    #[test]
+    fn test_engine_soft_drop_rows() {
+        // Soft drop is 20 times faster than normal gravity, so these are 2 ticks per row, 1 row per
+        // tick, and 5 rows per tick.
+        for gravity in [
+            Gravity::TicksPerRow(40),
+            Gravity::TicksPerRow(20),
+            Gravity::TicksPerRow(4),
+        ]
+        .iter()
+        {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+            let observer = Rc::new(RecordingObserver::new());
+            engine.add_observer(observer.clone());
+            engine.set_gravity(*gravity);
+            // O piece falls from rows 21 and 22 to rows 1 and 2.
+            let n_rows = 20;
+
+            // Hold soft drop until the piece reaches the floor, then keep holding it.
+            let mut ticks = 0;
+            while !matches!(engine.state, State::Lock(_)) {
+                engine.input_soft_drop();
+                engine.tick();
+                ticks += 1;
+                assert!(ticks <= 2 * n_rows + 1);
+            }
+            for _ in 0..20 {
+                engine.input_soft_drop();
+                assert!(matches!(engine.tick(), State::Lock(_)));
+            }
+
+            let soft_drops = observer.soft_drops.borrow();
+            assert_eq!(
+                soft_drops.iter().map(|n| u32::from(*n)).sum::<u32>(),
+                n_rows
+            );
+        }
+    }
+
+    #[test]
+    fn test_engine_auto_repeat_disabled() {
+        fn count_moves(engine: &mut BaseEngine) -> usize {
+            (0..100)
+                .filter(|_| {
+                    engine.input_move_left();
+                    engine.process_input().contains(&Action::MoveLeft)
+                })
+                .count()
+        }
+
+        let mut engine = BaseEngine::new();
+        assert!(count_moves(&mut engine) > 1);
+
+        // Release, then hold again with auto-repeat disabled.
+        engine.process_input();
+        engine.set_auto_repeat_enabled(false);
+        assert_eq!(count_moves(&mut engine), 1);
+    }
+
+    #[test]
+    fn test_engine_das_carry() {
+        /// Holds left while locking a piece, then returns the number of ticks between the first and
+        /// second move of the next piece.
+        fn ticks_between_moves(das_carry: bool) -> usize {
+            let mut engine =
+                BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+            engine.set_das_carry(das_carry);
+
+            // Charge auto-repeat, then hard drop while still holding left.
+            for _ in 0..20 {
+                engine.input_move_left();
+                engine.tick();
+            }
+            engine.input_move_left();
+            engine.input_hard_drop();
+            engine.tick();
+            engine.input_move_left();
+            assert!(matches!(engine.tick(), State::Falling(1)));
+
+            let mut moves = vec![];
+            let mut col = engine.current_piece.col;
+            for tick in 0..30 {
+                engine.input_move_left();
+                engine.tick();
+                if engine.current_piece.col != col {
+                    col = engine.current_piece.col;
+                    moves.push(tick);
+                }
+            }
+            moves[1] - moves[0]
+        }
+
+        // The held input keeps auto-repeating.
+        assert_eq!(ticks_between_moves(true), AUTO_REPEAT_RATE as usize);
+        // The held input is treated as a new press, so it waits for the auto-repeat delay.
+        assert_eq!(ticks_between_moves(false), AUTO_REPEAT_DELAY as usize - 1);
+    }
+
+    #[test]
+    fn test_engine_ticks_until_drop() {
+        let mut engine = BaseEngine::new();
+        engine.set_gravity(Gravity::TicksPerRow(30));
+
+        engine.state = State::Falling(10);
+        assert_eq!(engine.ticks_until_drop(), Option::Some(20));
+        engine.state = State::Falling(30);
+        assert_eq!(engine.ticks_until_drop(), Option::Some(0));
+
+        engine.state = State::Lock(10);
+        assert_eq!(engine.ticks_until_drop(), Option::None);
+
+        engine.state = State::Falling(10);
+        engine.set_gravity(Gravity::RowsPerTick(2));
+        assert_eq!(engine.ticks_until_drop(), Option::None);
+    }
+
+    #[test]
+    fn test_engine_resting_cells() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.next_piece();
+
+        // Falling piece is not resting.
+        assert!(engine.resting_cells().is_empty());
+
+        // Piece on the floor.
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        assert_eq!(engine.resting_cells(), vec![(1, 5), (1, 6)]);
+
+        // Piece partially supported by a block.
+        engine.next_piece();
+        engine.playfield.set(10, 6);
+        engine.drop(Playfield::TOTAL_HEIGHT);
+        assert_eq!(engine.resting_cells(), vec![(11, 6)]);
+    }
+
+    #[test]
+    fn test_engine_gravity_direction_up() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.set_gravity(Gravity::TicksPerRow(1));
+        engine.set_gravity_direction(GravityDirection::Up);
+
+        // Piece spawns on the bottom row.
+        assert_eq!(
+            engine.current_piece.get_cells(),
+            vec![(1, 5), (1, 6), (2, 5), (2, 6)]
+        );
+
+        // Tick until the piece rises to the top and locks.
+        let mut ticks = 0;
+        while engine.playfield.get(20, 5) == Space::Empty {
+            engine.tick();
+            ticks += 1;
+            assert!(ticks < 100);
+        }
+
+        for &(row, col) in [(19, 5), (19, 6), (20, 5), (20, 6)].iter() {
+            assert_eq!(engine.playfield.get(row, col), Space::Block);
+        }
+        assert_eq!(engine.playfield.get(1, 5), Space::Empty);
+        assert_eq!(engine.playfield.get(21, 5), Space::Empty);
+    }
+
+    #[test]
+    fn test_engine_fuzz() {
+        let mut rng = seeded_rng(0);
+        let mut engine = BaseEngine::new();
+
+        for _ in 0..100_000 {
+            match fuzz_tick(&mut engine, &mut rng) {
+                State::Spawn | State::Are(_) => {}
+                State::Falling(_) => assert!(!engine.has_collision()),
+                State::Lock(n) => {
+                    assert!(n <= LOCK_DELAY);
+                    assert!(!engine.has_collision());
+                }
+                State::LineClear(n) => assert!(n <= LINE_CLEAR_DELAY),
+                State::TopOut => engine = BaseEngine::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut rng = seeded_rng(0);
+        let mut engine = BaseEngine::with_recording();
+        let mut ticks = 0;
+        while ticks < 2_000 {
+            ticks += 1;
+            if let State::TopOut = fuzz_tick(&mut engine, &mut rng) {
+                break;
+            }
+        }
+
+        let replay = Replay::from_game(&engine).unwrap();
+        assert_eq!(replay.tick_count(), ticks);
+        assert!(replay.get_pieces().len() > PREVIEW_COUNT + 1);
+
+        let replayed = replay.play();
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(
+                    replayed.playfield.get(row, col),
+                    engine.playfield.get(row, col)
+                );
+            }
+        }
+        assert_eq!(replayed.current_piece, engine.current_piece);
+        assert_eq!(replayed.hold_piece, engine.hold_piece);
+        assert_eq!(replayed.get_next_pieces(), engine.get_next_pieces());
+
+        // Engines which are not recording cannot be replayed.
+        assert!(Replay::from_game(&BaseEngine::new()).is_none());
+
+        // Settings are replayed, including changes made during the game.
+        let mut engine = BaseEngine::with_recording();
+        engine.set_big_mode(true);
+        engine.set_entry_delay(5);
+        engine.set_preview_count(2);
+        engine.set_gravity(Gravity::RowsPerTick(2));
+        for tick in 0..1_000 {
+            if tick == 500 {
+                engine.set_gravity(Gravity::TicksPerRow(3));
+                engine.set_clear_gravity(ClearGravity::Cascade);
+            }
+            if let State::TopOut = fuzz_tick(&mut engine, &mut rng) {
+                break;
+            }
+        }
+
+        let replay = Replay::from_game(&engine).unwrap();
+        assert!(replay.engine().big_mode);
+        assert_eq!(replay.engine().entry_delay, 5);
+        assert_eq!(replay.engine().gravity, Gravity::RowsPerTick(2));
+        let replayed = replay.play();
+        assert_eq!(replayed.settings(), engine.settings());
+        assert_eq!(format!("{:?}", replayed), format!("{:?}", engine));
+        assert_eq!(replayed.current_piece, engine.current_piece);
+        assert_eq!(replayed.get_next_pieces(), engine.get_next_pieces());
+    }
+
+    #[test]
+    fn test_hash_recorder() {
+        let play = |seed| {
+            let mut rng = seeded_rng(0);
+            let mut engine = BaseEngine::with_seed(seed);
+            let recorder = Rc::new(HashRecorder::new());
+            engine.add_observer(recorder.clone());
+            for _ in 0..2_000 {
+                if let State::TopOut = fuzz_tick(&mut engine, &mut rng) {
+                    break;
+                }
+            }
+            recorder.get_hashes()
+        };
+
+        let hashes = play(1);
+        assert!(hashes.len() > 100);
+        assert_eq!(hashes, play(1));
+
+        // Different pieces eventually produce a different board.
+        assert_ne!(hashes, play(2));
+
+        // The hash does not depend on the platform or compiler version.
+        let recorder = HashRecorder::new();
+        recorder.on_tick(&Playfield::new(), CurrentPiece::new(Tetromino::T));
+        assert_eq!(recorder.get_hashes(), vec![18_074_066_761_792_545_331]);
+    }
+
+    #[test]
+    fn test_current_piece_ghost_on() {
+        let mut playfield = Playfield::new();
+        let piece = CurrentPiece::new(Tetromino::I);
+
+        // On an empty playfield, the ghost rests on the floor.
+        let ghost = piece.ghost_on(&playfield);
+        assert_eq!(ghost.lowest_occupied_row(), 1);
+        assert_eq!(ghost.get_col(), piece.get_col());
+
+        // The ghost rests on top of an obstacle.
+        playfield.set(7, 6);
+        let ghost = piece.ghost_on(&playfield);
+        assert_eq!(ghost.lowest_occupied_row(), 8);
+        assert_eq!(ghost.get_col(), piece.get_col());
+
+        // The same position the engine would use.
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        engine.playfield = playfield.clone();
+        assert_eq!(
+            engine.ghost_piece(),
+            engine.current_piece.ghost_on(&playfield)
+        );
+
+        // Pieces falling upwards stop at the top of the visible playfield.
+        engine.playfield = Playfield::new();
+        engine.set_gravity_direction(GravityDirection::Up);
+        let ghost = engine.ghost_piece();
+        let top_row = ghost.get_cells().iter().map(|&(row, _)| row).max();
+        assert_eq!(top_row, Option::Some(Playfield::VISIBLE_HEIGHT as i8));
+        assert_eq!(ghost.get_col(), engine.current_piece.get_col());
+    }
+
+    #[test]
+    fn test_engine_hold_and_hard_drop_same_tick() {
+        // Hold takes priority by default.
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.tick_with_inputs(&actions(&[Action::Hold, Action::HardDrop]));
+        assert_eq!(engine.get_hold_piece(), Option::Some(Tetromino::T));
+        assert_eq!(engine.get_playfield().block_count(), 0);
+        assert_eq!(engine.state, State::Spawn);
+
+        // Hard drop takes priority.
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        engine.set_hold_over_harddrop(false);
+        engine.tick_with_inputs(&actions(&[Action::Hold, Action::HardDrop]));
+        assert_eq!(engine.get_hold_piece(), Option::None);
+        assert_eq!(engine.get_playfield().block_count(), 4);
+        assert_eq!(engine.state, State::Spawn);
+    }
+
+    #[test]
+    fn test_engine_max_soft_drop_rows_per_tick() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        // Soft drop would otherwise drop the piece 20 rows per tick.
+        engine.set_gravity(Gravity::RowsPerTick(1));
+        engine.set_max_soft_drop_rows_per_tick(Option::Some(3));
+
+        let start_row = engine.current_piece.get_row();
+        engine.tick_with_inputs(&actions(&[Action::SoftDrop]));
+        assert_eq!(engine.current_piece.get_row(), start_row - 3);
+        engine.tick_with_inputs(&actions(&[Action::SoftDrop]));
+        assert_eq!(engine.current_piece.get_row(), start_row - 6);
+
+        // Normal gravity is not capped.
+        engine.set_max_soft_drop_rows_per_tick(Option::None);
+        engine.tick();
+        assert_eq!(engine.current_piece.get_row(), start_row - 7);
+
+        // A limit of 0 is ignored rather than stopping soft drop.
+        engine.set_max_soft_drop_rows_per_tick(Option::Some(3));
+        engine.set_max_soft_drop_rows_per_tick(Option::Some(0));
+        engine.tick_with_inputs(&actions(&[Action::SoftDrop]));
+        assert_eq!(engine.current_piece.lowest_occupied_row(), 1);
+    }
+
+    #[test]
+    fn test_engine_clear_gravity_cascade() {
+        fn setup(clear_gravity: ClearGravity) -> BaseEngine {
+            let mut engine = BaseEngine::new();
+            engine.set_clear_gravity(clear_gravity);
+            // Full row 1, with an overhang above an empty column 1 in row 3.
+            for col in 1..=Playfield::WIDTH {
+                engine.playfield.set(1, col);
+            }
+            engine.playfield.set(2, 2);
+            engine.playfield.set(3, 1);
+            engine.playfield.set(3, 2);
+            engine
+        }
+
+        // The overhang stays in place.
+        let mut engine = setup(ClearGravity::Naive);
+        assert_eq!(engine.clear_rows(), 1);
+        assert_eq!(engine.playfield.get(1, 1), Space::Empty);
+        assert_eq!(engine.playfield.get(2, 1), Space::Block);
+
+        // The overhang drops down.
+        let mut engine = setup(ClearGravity::Cascade);
+        assert_eq!(engine.clear_rows(), 1);
+        assert_eq!(engine.playfield.get(1, 1), Space::Block);
+        assert_eq!(engine.playfield.get(2, 1), Space::Empty);
+        assert_eq!(engine.playfield.get(1, 2), Space::Block);
+        assert_eq!(engine.playfield.get(2, 2), Space::Block);
+        assert_eq!(engine.playfield.block_count(), 3);
+
+        // Rows completed by falling blocks are also cleared, as separate line clears.
+        let mut engine = BaseEngine::new();
+        engine.set_clear_gravity(ClearGravity::Cascade);
+        let observer = Rc::new(RecordingObserver::new());
+        engine.add_observer(observer.clone());
+        for col in 2..=Playfield::WIDTH {
+            engine.playfield.set(1, col);
+        }
+        for col in 1..=Playfield::WIDTH {
+            engine.playfield.set(2, col);
+        }
+        engine.playfield.set(3, 1);
+        assert_eq!(engine.clear_rows(), 2);
+        assert_eq!(*observer.line_clears.borrow(), vec![1, 1]);
+        assert!(!engine.playfield.is_row_full(1));
+        assert_eq!(engine.playfield.block_count(), 0);
+    }
+
+    #[test]
+    fn test_engine_recent_kicks() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::I));
+        assert!(engine.get_recent_kicks().is_empty());
+
+        // Rotating in open space does not need a kick.
+        engine.tick_with_inputs(&actions(&[Action::RotateClockwise]));
+        assert_eq!(
+            engine.get_recent_kicks(),
+            vec![(Rotation::Spawn, Rotation::Clockwise, (0, 0))]
+        );
+
+        engine.tick();
+
+        // Move the vertical I against the left wall, then rotate so that it must be kicked right.
+        let mut piece = engine.current_piece;
+        while !engine.has_collision_with_piece(piece) {
+            engine.current_piece = piece;
+            piece.col -= 1;
+        }
+        engine.tick_with_inputs(&actions(&[Action::RotateClockwise]));
+        assert_eq!(
+            engine.get_recent_kicks(),
+            vec![
+                (Rotation::Spawn, Rotation::Clockwise, (0, 0)),
+                (Rotation::Clockwise, Rotation::OneEighty, (2, 0)),
+            ]
+        );
+
+        // Only the most recent kicks are kept.
+        for _ in 0..RECENT_KICK_COUNT {
+            engine.tick_with_inputs(&actions(&[Action::RotateCounterClockwise]));
+            engine.tick();
+        }
+        assert_eq!(engine.get_recent_kicks().len(), RECENT_KICK_COUNT);
+        assert_ne!(
+            engine.get_recent_kicks()[0],
+            (Rotation::Spawn, Rotation::Clockwise, (0, 0))
+        );
+    }
+
+    #[test]
+    fn test_engine_soft_drop_rate() {
+        let mut engine = BaseEngine::new();
+        let soft_drop_ticks = |engine: &mut BaseEngine| {
+            (1..=9)
+                .filter(|_| {
+                    engine.input_soft_drop();
+                    engine.process_input().contains(&Action::SoftDrop)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Soft drop acts on every tick by default.
+        assert_eq!(soft_drop_ticks(&mut engine), (1..=9).collect::<Vec<_>>());
+
+        // Release soft drop, then soft drop every third tick.
+        engine.process_input();
+        engine.set_soft_drop_rate(3);
+        assert_eq!(soft_drop_ticks(&mut engine), vec![1, 4, 7]);
+    }
+
+    #[test]
+    fn test_engine_submit_inputs() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::T));
+        let start = engine.current_piece;
+
+        engine.submit_inputs(&[Action::MoveLeft, Action::RotateClockwise]);
+        engine.tick();
+        assert_eq!(engine.current_piece.get_col(), start.get_col() - 1);
+        assert_eq!(
+            engine.current_piece.get_piece().get_rotation(),
+            &Rotation::Clockwise
+        );
+
+        // The inputs only apply to a single tick.
+        assert!(engine.current_tick_inputs.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_engine_wasted_inputs() {
+        let mut engine =
+            BaseEngine::with_tetromino_generator(Box::new(SingleTetrominoGenerator::O));
+        engine.tick_with_inputs(&actions(&[Action::SnapLeft]));
+        engine.tick();
+        assert_eq!(engine.get_effective_inputs(), 1);
+        assert_eq!(engine.get_wasted_inputs(), 0);
+
+        // The piece is against the wall, so moving left has no effect.
+        engine.tick_with_inputs(&actions(&[Action::MoveLeft]));
+        engine.tick();
+        assert_eq!(engine.get_effective_inputs(), 1);
+        assert_eq!(engine.get_wasted_inputs(), 1);
+
+        engine.tick_with_inputs(&actions(&[Action::MoveRight]));
+        assert_eq!(engine.get_effective_inputs(), 2);
+        assert_eq!(engine.get_wasted_inputs(), 1);
+    }
+
+    #[test]
+    fn test_engine_next_pieces_filled_lazily() {
+        use std::cell::Cell;
+
+        struct CountingGenerator {
+            count: Rc<Cell<u32>>,
+        }
+
+        impl TetrominoGenerator for CountingGenerator {
+            fn next(&self) -> Tetromino {
+                self.count.set(self.count.get() + 1);
+                Tetromino::T
+            }
+        }
+
+        let count = Rc::new(Cell::new(0));
+        let engine = BaseEngine::with_tetromino_generator(Box::new(CountingGenerator {
+            count: count.clone(),
+        }));
+        // Only the current piece is generated.
+        assert_eq!(count.get(), 1);
+
+        assert_eq!(engine.get_next_pieces().len(), PREVIEW_COUNT);
+        assert_eq!(count.get(), 1 + PREVIEW_COUNT as u32);
+        engine.get_next_pieces();
+        assert_eq!(count.get(), 1 + PREVIEW_COUNT as u32);
+
+        // Spawning the next piece also fills the queue.
+        let count = Rc::new(Cell::new(0));
+        let mut engine = BaseEngine::with_tetromino_generator(Box::new(CountingGenerator {
+            count: count.clone(),
+        }));
+        engine.next_piece();
+        assert_eq!(count.get(), 2 + PREVIEW_COUNT as u32);
+    }
+
+    #[test]
+    fn test_engine_next_pieces_multiple_iterators() {
+        let engine = BaseEngine::new();
+        let pieces: Vec<(Tetromino, Tetromino)> = engine
+            .next_pieces_iter()
+            .zip(engine.next_pieces_iter())
+            .collect();
+        assert_eq!(pieces.len(), PREVIEW_COUNT);
+        assert!(pieces.iter().all(|(a, b)| a == b));
+
+        let mut iter = engine.next_pieces_iter();
+        let first = iter.next().unwrap();
+        assert_eq!(engine.pieces_until(first), Option::Some(0));
+        assert_eq!(engine.get_next_pieces()[1..], iter.collect::<Vec<_>>()[..]);
+    }
 }
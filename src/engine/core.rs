@@ -1,11 +1,73 @@
+use std::collections::HashMap;
 use std::fmt;
 
-/// The playfield where blocks are placed.
-#[derive(Clone, Copy)]
+/// The playfield where blocks are placed. The grid is stored on the heap, so the playfield is not
+/// `Copy`; it should be borrowed, or cloned explicitly.
+#[derive(Clone)]
 pub struct Playfield {
-    grid: [[Space; 10]; 40],
+    grid: Box<[[Space; 10]; 40]>,
+    // The tetromino which placed each block, if known.
+    origins: Box<[[Option<Tetromino>; 10]; 40]>,
 }
 
+/// An error from accessing the playfield.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PlayfieldError {
+    /// The row is not between 1 and `Playfield::TOTAL_HEIGHT`.
+    RowOutOfBounds(u8),
+    /// The column is not between 1 and `Playfield::WIDTH`.
+    ColOutOfBounds(u8),
+}
+
+impl fmt::Display for PlayfieldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlayfieldError::RowOutOfBounds(row) => write!(
+                f,
+                "row {} is not between 1 and {}.",
+                row,
+                Playfield::TOTAL_HEIGHT
+            ),
+            PlayfieldError::ColOutOfBounds(col) => {
+                write!(f, "col {} is not between 1 and {}.", col, Playfield::WIDTH)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlayfieldError {}
+
+/// An error from decoding a playfield code.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DecodeError {
+    /// The code contains a character which is not in the URL-safe base64 alphabet.
+    InvalidCharacter(char),
+    /// The code does not decode to a whole number of rows.
+    InvalidLength,
+    /// The code contains more than `Playfield::TOTAL_HEIGHT` rows.
+    TooManyRows,
+    /// The specified row has a block beyond `Playfield::WIDTH` columns.
+    InvalidRow(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::InvalidCharacter(c) => write!(f, "invalid character '{}'.", c),
+            DecodeError::InvalidLength => write!(f, "code has an invalid length."),
+            DecodeError::TooManyRows => {
+                write!(f, "code has more than {} rows.", Playfield::TOTAL_HEIGHT)
+            }
+            DecodeError::InvalidRow(row) => write!(f, "row {} has too many columns.", row),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+const CODE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
 /// A space in the playfield.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Space {
@@ -13,6 +75,16 @@ pub enum Space {
     Block,
 }
 
+/// The number of rows a T-spin in a T slot would clear.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TSlotKind {
+    Single,
+    Double,
+    /// Only possible with a vertical T, in a slot with an overhang covering the notch for the
+    /// T's middle block.
+    Triple,
+}
+
 impl Playfield {
     pub const WIDTH: u8 = 10;
     pub const VISIBLE_HEIGHT: u8 = 20;
@@ -21,7 +93,12 @@ impl Playfield {
     /// Creates a new empty playfield.
     pub fn new() -> Playfield {
         Playfield {
-            grid: [[Space::Empty; Playfield::WIDTH as usize]; Playfield::TOTAL_HEIGHT as usize],
+            grid: Box::new(
+                [[Space::Empty; Playfield::WIDTH as usize]; Playfield::TOTAL_HEIGHT as usize],
+            ),
+            origins: Box::new(
+                [[Option::None; Playfield::WIDTH as usize]; Playfield::TOTAL_HEIGHT as usize],
+            ),
         }
     }
 
@@ -31,26 +108,534 @@ impl Playfield {
         self.grid[row as usize - 1][col as usize - 1]
     }
 
+    /// Gets the tetromino which placed the block at the specified row and column. Returns
+    /// `Option::None` if the space is empty or if the block was not placed by a tetromino.
+    pub fn get_tetromino(&self, row: u8, col: u8) -> Option<Tetromino> {
+        Playfield::check_index(row, col);
+        self.origins[row as usize - 1][col as usize - 1]
+    }
+
+    /// Gets an index suitable for grouping blocks by color. Blocks placed by a tetromino use the
+    /// index of that tetromino. Other blocks use index 7. Returns `Option::None` if the space is
+    /// empty.
+    pub fn color_index(&self, row: u8, col: u8) -> Option<u8> {
+        match (self.get(row, col), self.get_tetromino(row, col)) {
+            (Space::Empty, _) => Option::None,
+            (Space::Block, Option::Some(shape)) => Option::Some(shape.index()),
+            (Space::Block, Option::None) => Option::Some(7),
+        }
+    }
+
     // Sets the space at the specified row and column to a block.
     pub fn set(&mut self, row: u8, col: u8) {
         Playfield::check_index(row, col);
         self.grid[row as usize - 1][col as usize - 1] = Space::Block;
+        self.origins[row as usize - 1][col as usize - 1] = Option::None;
+    }
+
+    /// Sets the space at the specified row and column to a block placed by the specified tetromino.
+    pub fn set_tetromino(&mut self, row: u8, col: u8, shape: Tetromino) {
+        Playfield::check_index(row, col);
+        self.grid[row as usize - 1][col as usize - 1] = Space::Block;
+        self.origins[row as usize - 1][col as usize - 1] = Option::Some(shape);
     }
 
     // Clears the space at the specified row and column.
     pub fn clear(&mut self, row: u8, col: u8) {
         Playfield::check_index(row, col);
-        self.grid[row as usize - 1][col as usize - 1] = Space::Empty
+        self.grid[row as usize - 1][col as usize - 1] = Space::Empty;
+        self.origins[row as usize - 1][col as usize - 1] = Option::None;
+    }
+
+    /// Sets the space at the specified row and column to a block. Returns an error, without
+    /// modifying the playfield, if the row or column is out of bounds.
+    pub fn try_set(&mut self, row: u8, col: u8) -> Result<(), PlayfieldError> {
+        Playfield::validate_index(row, col)?;
+        self.set(row, col);
+        Result::Ok(())
+    }
+
+    /// Clears the space at the specified row and column. Returns an error, without modifying the
+    /// playfield, if the row or column is out of bounds.
+    pub fn try_clear(&mut self, row: u8, col: u8) -> Result<(), PlayfieldError> {
+        Playfield::validate_index(row, col)?;
+        self.clear(row, col);
+        Result::Ok(())
+    }
+
+    /// Returns a copy of the rows from `from_row` to `to_row`, inclusive, starting with `from_row`.
+    /// Returns no rows if `from_row` is above `to_row`.
+    pub fn subgrid(&self, from_row: u8, to_row: u8) -> Vec<[Space; 10]> {
+        Playfield::check_index(from_row, 1);
+        Playfield::check_index(to_row, 1);
+        (from_row..=to_row)
+            .map(|row| self.grid[row as usize - 1])
+            .collect()
+    }
+
+    /// Copies the contents of one row to another row.
+    pub fn copy_row(&mut self, from_row: u8, to_row: u8) {
+        Playfield::check_index(from_row, 1);
+        Playfield::check_index(to_row, 1);
+        self.grid[to_row as usize - 1] = self.grid[from_row as usize - 1];
+        self.origins[to_row as usize - 1] = self.origins[from_row as usize - 1];
+    }
+
+    /// Clears any rows that are full and drops the rows above them down. Returns the number of rows
+    /// cleared.
+    pub fn remove_full_rows(&mut self) -> u8 {
+        // Construct a list of all row that will NOT be cleared.
+        let non_full_rows: Vec<u8> = (1..=Playfield::TOTAL_HEIGHT)
+            .filter(|row| !self.is_row_full(*row))
+            .collect();
+
+        // Don't do anything if no rows are full
+        if non_full_rows.len() == Playfield::TOTAL_HEIGHT as usize {
+            return 0;
+        }
+
+        // Copy non-full rows to next available row. Since full rows are not in the list, this has
+        // the effect of overwriting the full rows.
+        let mut current_row = 1;
+        for row in non_full_rows.iter() {
+            // Copy non-full row to current row.
+            self.copy_row(*row, current_row);
+            current_row += 1;
+        }
+
+        // Clear remaining rows.
+        for row in current_row..Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                self.clear(row, col);
+            }
+        }
+
+        Playfield::TOTAL_HEIGHT - non_full_rows.len() as u8
+    }
+
+    /// Drops every block in each column down until it rests on the floor or another block, i.e. so
+    /// that no column contains an empty space below a block.
+    pub fn apply_column_gravity(&mut self) {
+        for col in 0..Playfield::WIDTH as usize {
+            let mut next_row = 0;
+            for row in 0..Playfield::TOTAL_HEIGHT as usize {
+                if self.grid[row][col] == Space::Block {
+                    if row != next_row {
+                        self.grid[next_row][col] = Space::Block;
+                        self.origins[next_row][col] = self.origins[row][col];
+                        self.grid[row][col] = Space::Empty;
+                        self.origins[row][col] = Option::None;
+                    }
+                    next_row += 1;
+                }
+            }
+        }
+    }
+
+    /// Moves every row up by one, discarding the top row, then fills the bottom row with garbage
+    /// blocks except for the specified hole column.
+    pub fn add_garbage_row(&mut self, hole_col: u8) {
+        Playfield::check_index(1, hole_col);
+        for row in (1..Playfield::TOTAL_HEIGHT).rev() {
+            self.copy_row(row, row + 1);
+        }
+        for col in 1..=Playfield::WIDTH {
+            if col == hole_col {
+                self.clear(1, col);
+            }
+            else {
+                self.set(1, col);
+            }
+        }
+    }
+
+    /// Rotates the visible playfield by 180 degrees, so that the block at (row, col) moves to
+    /// (`VISIBLE_HEIGHT` + 1 - row, `WIDTH` + 1 - col). Rows above the visible playfield are
+    /// unchanged.
+    pub fn rotate_180(&mut self) {
+        let height = Playfield::VISIBLE_HEIGHT as usize;
+        self.grid[..height].reverse();
+        self.origins[..height].reverse();
+        for row in self.grid[..height].iter_mut() {
+            row.reverse();
+        }
+        for row in self.origins[..height].iter_mut() {
+            row.reverse();
+        }
+    }
+
+    /// Returns a playfield with a block in every space which contains a block in either this
+    /// playfield or the other playfield. Where both contain a block, the tetromino which placed the
+    /// block in this playfield is kept.
+    pub fn union(&self, other: &Playfield) -> Playfield {
+        let mut playfield = self.clone();
+        for row in 0..Playfield::TOTAL_HEIGHT as usize {
+            for col in 0..Playfield::WIDTH as usize {
+                if playfield.grid[row][col] == Space::Empty && other.grid[row][col] == Space::Block
+                {
+                    playfield.grid[row][col] = Space::Block;
+                    playfield.origins[row][col] = other.origins[row][col];
+                }
+            }
+        }
+        playfield
+    }
+
+    /// Returns a copy of this playfield with the blocks of the specified piece set. Blocks outside
+    /// of the playfield are ignored.
+    pub fn with_piece(&self, piece: CurrentPiece) -> Playfield {
+        let mut playfield = self.clone();
+        let shape = *piece.get_piece().get_shape();
+        for (row, col) in piece.get_cells() {
+            if row >= 1
+                && row <= Playfield::TOTAL_HEIGHT as i8
+                && col >= 1
+                && col <= Playfield::WIDTH as i8
+            {
+                playfield.set_tetromino(row as u8, col as u8, shape);
+            }
+        }
+        playfield
+    }
+
+    /// Returns whether every space in the specified row contains a block.
+    pub fn is_row_full(&self, row: u8) -> bool {
+        Playfield::check_index(row, 1);
+        self.grid[row as usize - 1]
+            .iter()
+            .all(|space| *space == Space::Block)
+    }
+
+    /// Returns the index of every full row, from bottom to top.
+    pub fn full_rows(&self) -> Vec<u8> {
+        (1..=Playfield::TOTAL_HEIGHT)
+            .filter(|row| self.is_row_full(*row))
+            .collect()
+    }
+
+    /// Returns whether the specified position is outside of the playfield or contains a block.
+    pub fn is_occupied(&self, row: i8, col: i8) -> bool {
+        row < 1
+            || row > Playfield::TOTAL_HEIGHT as i8
+            || col < 1
+            || col > Playfield::WIDTH as i8
+            || self.get(row as u8, col as u8) == Space::Block
+    }
+
+    /// Returns the positions where a T tetromino could be placed to perform a T-spin, as the
+    /// (row, col) of the center of the T and its rotation. The T must fit in an empty space resting
+    /// on a block or the floor, with at least three of the four corners around its center occupied,
+    /// including both corners on the side it is pointing towards, so that its point fills a notch.
+    /// At least one of its blocks must be open to the top of the playfield. If more than one
+    /// rotation fits around the same center, only the rotations which clear the most rows are
+    /// returned.
+    pub fn find_tslots(&self) -> Vec<(u8, u8, Rotation)> {
+        let mut tslots = vec![];
+        let mut piece = Piece::new(Tetromino::T);
+        for _ in 0..4 {
+            let rotation = *piece.get_rotation();
+            let mut cells = vec![];
+            for (bb_row_index, bb_row) in piece.get_bounding_box().iter().enumerate() {
+                for (bb_col_index, bb_space) in bb_row.iter().enumerate() {
+                    if bb_space == &Space::Block {
+                        cells.push((bb_row_index as i8, bb_col_index as i8));
+                    }
+                }
+            }
+
+            for center_row in 1..=Playfield::TOTAL_HEIGHT {
+                for center_col in 1..=Playfield::WIDTH {
+                    // The center of a T is at row 2, col 1 of its bounding box.
+                    let row = center_row as i8 - 2;
+                    let col = center_col as i8 - 1;
+                    let blocks: Vec<(i8, i8)> =
+                        cells.iter().map(|(r, c)| (row + r, col + c)).collect();
+
+                    let fits = blocks.iter().all(|(r, c)| !self.is_occupied(*r, *c));
+                    let is_resting = blocks.iter().any(|(r, c)| {
+                        !blocks.contains(&(r - 1, *c)) && self.is_occupied(r - 1, *c)
+                    });
+                    let corners = rotation.t_corner_offsets();
+                    let n_corners = corners
+                        .iter()
+                        .filter(|(r, c)| self.is_occupied(row + r, col + c))
+                        .count();
+                    let is_notch = corners[..2]
+                        .iter()
+                        .all(|(r, c)| self.is_occupied(row + r, col + c));
+                    let is_open = blocks.iter().any(|(r, c)| {
+                        (r + 1..=Playfield::TOTAL_HEIGHT as i8).all(|above| {
+                            blocks.contains(&(above, *c)) || !self.is_occupied(above, *c)
+                        })
+                    });
+
+                    if fits && is_resting && n_corners >= 3 && is_notch && is_open {
+                        tslots.push((center_row, center_col, rotation));
+                    }
+                }
+            }
+
+            piece.rotate_cw();
+        }
+
+        let mut most_rows_cleared = HashMap::new();
+        let tslots: Vec<(u8, u8, Rotation, usize)> = tslots
+            .into_iter()
+            .map(|(row, col, rotation)| {
+                let n_rows = self.tslot_rows_cleared(row, col, rotation);
+                let most = most_rows_cleared.entry((row, col)).or_insert(n_rows);
+                *most = (*most).max(n_rows);
+                (row, col, rotation, n_rows)
+            })
+            .collect();
+        tslots
+            .into_iter()
+            .filter(|(row, col, _, n_rows)| most_rows_cleared[&(*row, *col)] == *n_rows)
+            .map(|(row, col, rotation, _)| (row, col, rotation))
+            .collect()
+    }
+
+    /// Returns the T slots found by `find_tslots` which would clear at least one row, along with the
+    /// kind of T-spin each would result in.
+    pub fn find_classified_tslots(&self) -> Vec<(u8, u8, Rotation, TSlotKind)> {
+        self.find_tslots()
+            .into_iter()
+            .filter_map(|(row, col, rotation)| {
+                self.tslot_kind(row, col, rotation)
+                    .map(|kind| (row, col, rotation, kind))
+            })
+            .collect()
+    }
+
+    /// Returns the kind of T-spin which placing a T with its center at the specified (row, col)
+    /// and the specified rotation would result in, based on the number of rows it would clear.
+    /// Returns `Option::None` if no rows would be cleared. Does not check whether the T fits.
+    pub fn tslot_kind(
+        &self,
+        center_row: u8,
+        center_col: u8,
+        rotation: Rotation,
+    ) -> Option<TSlotKind> {
+        match self.tslot_rows_cleared(center_row, center_col, rotation) {
+            0 => Option::None,
+            1 => Option::Some(TSlotKind::Single),
+            2 => Option::Some(TSlotKind::Double),
+            _ => Option::Some(TSlotKind::Triple),
+        }
+    }
+
+    /// Returns the number of rows which placing a T with its center at the specified (row, col) and
+    /// the specified rotation would clear.
+    fn tslot_rows_cleared(&self, center_row: u8, center_col: u8, rotation: Rotation) -> usize {
+        let mut piece = Piece::new(Tetromino::T);
+        while *piece.get_rotation() != rotation {
+            piece.rotate_cw();
+        }
+
+        let mut playfield = self.clone();
+        let mut rows = vec![];
+        for (bb_row_index, bb_row) in piece.get_bounding_box().iter().enumerate() {
+            for (bb_col_index, bb_space) in bb_row.iter().enumerate() {
+                if bb_space != &Space::Block {
+                    continue;
+                }
+                // The center of a T is at row 2, col 1 of its bounding box.
+                let row = center_row
+                    .checked_add(bb_row_index as u8)
+                    .and_then(|row| row.checked_sub(2));
+                let col = center_col
+                    .checked_add(bb_col_index as u8)
+                    .and_then(|col| col.checked_sub(1));
+                match (row, col) {
+                    (Option::Some(row), Option::Some(col))
+                        if Playfield::validate_index(row, col).is_ok() =>
+                    {
+                        playfield.set(row, col);
+                        rows.push(row);
+                    }
+                    // Blocks outside of the playfield can not complete a row.
+                    _ => (),
+                }
+            }
+        }
+        rows.dedup();
+
+        rows.iter()
+            .filter(|row| playfield.is_row_full(**row))
+            .count()
+    }
+
+    /// Returns the number of blocks in the playfield.
+    pub fn block_count(&self) -> u32 {
+        self.grid
+            .iter()
+            .flat_map(|row| row.iter())
+            .filter(|space| **space == Space::Block)
+            .count() as u32
+    }
+
+    /// Returns the highest row containing a block, or `Option::None` if the playfield is empty.
+    pub fn highest_block_row(&self) -> Option<u8> {
+        for row in (1..=Playfield::TOTAL_HEIGHT).rev() {
+            for col in 1..=Playfield::WIDTH {
+                if self.get(row, col) == Space::Block {
+                    return Option::Some(row);
+                }
+            }
+        }
+        Option::None
+    }
+
+    /// Returns the number of empty spaces which have a block somewhere above them in the same
+    /// column.
+    pub fn hole_count(&self) -> u32 {
+        let mut holes = 0;
+        for col in 1..=Playfield::WIDTH {
+            let mut covered = false;
+            for row in (1..=Playfield::TOTAL_HEIGHT).rev() {
+                match self.get(row, col) {
+                    Space::Block => covered = true,
+                    Space::Empty if covered => holes += 1,
+                    Space::Empty => (),
+                }
+            }
+        }
+        holes
+    }
+
+    /// Returns the number of columns in the longest run of adjacent columns whose highest blocks
+    /// are in the same row, i.e. the widest flat area on the surface of the stack.
+    pub fn flat_surface_width(&self) -> u8 {
+        let heights: Vec<u8> = (1..=Playfield::WIDTH)
+            .map(|col| {
+                (1..=Playfield::TOTAL_HEIGHT)
+                    .rev()
+                    .find(|row| self.get(*row, col) == Space::Block)
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let mut longest = 1;
+        let mut current = 1;
+        for pair in heights.windows(2) {
+            if pair[0] == pair[1] {
+                current += 1;
+                longest = longest.max(current);
+            }
+            else {
+                current = 1;
+            }
+        }
+        longest
+    }
+
+    /// Returns whether no tetromino can spawn, because the spawn position of every tetromino is
+    /// blocked. This assumes pieces spawn in the position given by `CurrentPiece::new`; use
+    /// `BaseEngine::is_topped_out` to account for the engine's spawn settings.
+    pub fn is_topped_out(&self) -> bool {
+        Tetromino::ALL.iter().all(|shape| {
+            CurrentPiece::new(*shape)
+                .cells()
+                .any(|(row, col)| self.is_occupied(row, col))
+        })
+    }
+
+    /// Encodes the blocks of this playfield as a short code which can be shared and decoded with
+    /// `from_code`. Each row, up to the highest block, is stored as a two byte bitmask, and the
+    /// bytes are encoded as URL-safe base64 without padding. The tetromino which placed each block
+    /// is not included.
+    pub fn to_code(&self) -> String {
+        let height = self.highest_block_row().unwrap_or(0);
+        let mut bytes = Vec::with_capacity(2 * height as usize);
+        for row in 1..=height {
+            let mask = (1..=Playfield::WIDTH)
+                .filter(|col| self.get(row, *col) == Space::Block)
+                .fold(0u16, |mask, col| mask | 1 << (col - 1));
+            bytes.push((mask >> 8) as u8);
+            bytes.push(mask as u8);
+        }
+
+        let mut code = String::with_capacity(bytes.len() / 3 * 4 + 4);
+        for chunk in bytes.chunks(3) {
+            let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+                group | u32::from(*byte) << (16 - 8 * i)
+            });
+            for i in 0..=chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3F;
+                code.push(char::from(CODE_ALPHABET[index as usize]));
+            }
+        }
+        code
+    }
+
+    /// Decodes a playfield from a code created by `to_code`.
+    pub fn from_code(code: &str) -> Result<Playfield, DecodeError> {
+        let mut values = Vec::with_capacity(code.len());
+        for c in code.chars() {
+            match CODE_ALPHABET.iter().position(|a| char::from(*a) == c) {
+                Option::Some(value) => values.push(value as u32),
+                Option::None => return Result::Err(DecodeError::InvalidCharacter(c)),
+            }
+        }
+        if values.len() % 4 == 1 {
+            return Result::Err(DecodeError::InvalidLength);
+        }
+
+        let mut bytes = Vec::with_capacity(values.len() * 3 / 4);
+        for chunk in values.chunks(4) {
+            let group = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |group, (i, value)| group | value << (18 - 6 * i));
+            for i in 0..chunk.len() - 1 {
+                bytes.push((group >> (16 - 8 * i)) as u8);
+            }
+        }
+        if bytes.len() % 2 != 0 {
+            return Result::Err(DecodeError::InvalidLength);
+        }
+        if bytes.len() / 2 > Playfield::TOTAL_HEIGHT as usize {
+            return Result::Err(DecodeError::TooManyRows);
+        }
+
+        let mut playfield = Playfield::new();
+        for (i, row_bytes) in bytes.chunks(2).enumerate() {
+            let row = i as u8 + 1;
+            let mask = u16::from(row_bytes[0]) << 8 | u16::from(row_bytes[1]);
+            if mask >> Playfield::WIDTH != 0 {
+                return Result::Err(DecodeError::InvalidRow(row));
+            }
+            for col in 1..=Playfield::WIDTH {
+                if mask & 1 << (col - 1) != 0 {
+                    playfield.set(row, col);
+                }
+            }
+        }
+        Result::Ok(playfield)
     }
 
     /// Panics if row or column are out of bounds.
     fn check_index(row: u8, col: u8) {
+        match Playfield::validate_index(row, col) {
+            Result::Err(PlayfieldError::RowOutOfBounds(_)) => {
+                panic!("row must be be between 1 and 40.")
+            }
+            Result::Err(PlayfieldError::ColOutOfBounds(_)) => {
+                panic!("col must be between 1 and 10.")
+            }
+            Result::Ok(()) => (),
+        }
+    }
+
+    /// Returns an error if row or column are out of bounds.
+    fn validate_index(row: u8, col: u8) -> Result<(), PlayfieldError> {
         if row < 1 || row > Playfield::TOTAL_HEIGHT {
-            panic!("row must be be between 1 and 40.");
+            return Result::Err(PlayfieldError::RowOutOfBounds(row));
         }
         if col < 1 || col > Playfield::WIDTH {
-            panic!("col must be between 1 and 10.");
+            return Result::Err(PlayfieldError::ColOutOfBounds(col));
         }
+        Result::Ok(())
     }
 }
 
@@ -98,8 +683,75 @@ pub enum Tetromino {
     L,
 }
 
+impl Tetromino {
+    /// Every tetromino, in index order.
+    pub const ALL: [Tetromino; 7] = [
+        Tetromino::I,
+        Tetromino::O,
+        Tetromino::T,
+        Tetromino::S,
+        Tetromino::Z,
+        Tetromino::J,
+        Tetromino::L,
+    ];
+
+    /// Returns a stable index between 0 and 6 for this tetromino.
+    pub fn index(self) -> u8 {
+        match self {
+            Tetromino::I => 0,
+            Tetromino::O => 1,
+            Tetromino::T => 2,
+            Tetromino::S => 3,
+            Tetromino::Z => 4,
+            Tetromino::J => 5,
+            Tetromino::L => 6,
+        }
+    }
+
+    /// Returns the standard RGB color for this tetromino.
+    pub fn color(self) -> [u8; 3] {
+        match self {
+            Tetromino::I => [0, 255, 255],
+            Tetromino::O => [255, 255, 0],
+            Tetromino::T => [128, 0, 128],
+            Tetromino::S => [0, 255, 0],
+            Tetromino::Z => [255, 0, 0],
+            Tetromino::J => [0, 0, 255],
+            Tetromino::L => [255, 128, 0],
+        }
+    }
+
+    /// Returns the letter of this tetromino.
+    pub fn to_char(self) -> char {
+        match self {
+            Tetromino::I => 'I',
+            Tetromino::O => 'O',
+            Tetromino::T => 'T',
+            Tetromino::S => 'S',
+            Tetromino::Z => 'Z',
+            Tetromino::J => 'J',
+            Tetromino::L => 'L',
+        }
+    }
+
+    /// Returns the tetromino with the specified letter, or `Option::None` if it is not the letter
+    /// of a tetromino. Letters must be uppercase.
+    pub fn from_char(c: char) -> Option<Tetromino> {
+        match c {
+            'I' => Option::Some(Tetromino::I),
+            'O' => Option::Some(Tetromino::O),
+            'T' => Option::Some(Tetromino::T),
+            'S' => Option::Some(Tetromino::S),
+            'Z' => Option::Some(Tetromino::Z),
+            'J' => Option::Some(Tetromino::J),
+            'L' => Option::Some(Tetromino::L),
+            _ => Option::None,
+        }
+    }
+}
+
 /// The rotation state of a tetromino.
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Rotation {
     /// The default rotation when a piece is spawned.
     Spawn,
@@ -129,9 +781,21 @@ impl Rotation {
             Rotation::CounterClockwise => Rotation::OneEighty,
         }
     }
+
+    /// Returns the (row, col) offsets of the four corners around the center of a T tetromino with
+    /// this rotation, relative to its bounding box. The first two corners are on the side the T is
+    /// pointing towards.
+    pub fn t_corner_offsets(self) -> [(i8, i8); 4] {
+        match self {
+            Rotation::Spawn => [(3, 0), (3, 2), (1, 0), (1, 2)],
+            Rotation::Clockwise => [(3, 2), (1, 2), (3, 0), (1, 0)],
+            Rotation::OneEighty => [(1, 2), (1, 0), (3, 2), (3, 0)],
+            Rotation::CounterClockwise => [(1, 0), (3, 0), (1, 2), (3, 2)],
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Piece {
     shape: Tetromino,
     rotation: Rotation,
@@ -186,6 +850,24 @@ impl Piece {
         self.rotation = self.rotation.ccw();
     }
 
+    /// Returns the smallest bounds containing every block of this piece, as `(min_row, max_row,
+    /// min_col, max_col)`. Offsets are relative to the bounding box, with row 0 at the bottom.
+    pub fn tight_bounds(&self) -> (i8, i8, i8, i8) {
+        let mut bounds = (i8::MAX, i8::MIN, i8::MAX, i8::MIN);
+        for (row, bb_row) in self.get_bounding_box().iter().enumerate() {
+            for (col, bb_space) in bb_row.iter().enumerate() {
+                if bb_space == &Space::Block {
+                    let (row, col) = (row as i8, col as i8);
+                    bounds.0 = bounds.0.min(row);
+                    bounds.1 = bounds.1.max(row);
+                    bounds.2 = bounds.2.min(col);
+                    bounds.3 = bounds.3.max(col);
+                }
+            }
+        }
+        bounds
+    }
+
     pub fn get_bounding_box(self) -> [[Space; 4]; 4] {
         match self {
             Piece {
@@ -417,6 +1099,129 @@ impl Piece {
     }
 }
 
+/// The current piece on the playfield.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct CurrentPiece {
+    pub(crate) piece: Piece,
+    // Position of lower-left corner of bounding box.
+    pub(crate) row: i8,
+    pub(crate) col: i8,
+    // Number of rows and columns occupied by each block.
+    pub(crate) scale: i8,
+}
+
+/// Returns the column of the bounding box of a newly spawned piece so that it is centered on a
+/// playfield of the specified width. Pieces with an odd width are placed left of center.
+pub(crate) fn spawn_col(width: u8) -> i8 {
+    (width / 2) as i8 - 1
+}
+
+impl CurrentPiece {
+    /// Creates a new piece in spawn position.
+    pub fn new(shape: Tetromino) -> CurrentPiece {
+        CurrentPiece {
+            piece: Piece::new(shape),
+            row: 19,
+            col: spawn_col(Playfield::WIDTH),
+            scale: 1,
+        }
+    }
+
+    pub(crate) fn rotate_cw(&mut self) {
+        self.piece.rotate_cw();
+    }
+
+    pub(crate) fn rotate_ccw(&mut self) {
+        self.piece.rotate_ccw();
+    }
+
+    pub fn get_piece(self) -> Piece {
+        self.piece
+    }
+
+    pub fn get_bounding_box(self) -> [[Space; 4]; 4] {
+        self.piece.get_bounding_box()
+    }
+
+    pub fn get_row(self) -> i8 {
+        self.row
+    }
+
+    pub fn get_col(self) -> i8 {
+        self.col
+    }
+
+    /// Moves the lower-left corner of the bounding box to the specified position. Does not check
+    /// for collisions.
+    pub fn set_position(&mut self, row: i8, col: i8) {
+        self.row = row;
+        self.col = col;
+    }
+
+    /// Returns the row of the lowest block of this piece in the playfield. This may be above the
+    /// row of the bounding box if its bottom rows are empty.
+    pub fn lowest_occupied_row(self) -> i8 {
+        let (min_row, _, _, _) = self.piece.tight_bounds();
+        self.row + self.scale * min_row
+    }
+
+    /// Returns the (row, col) position of each block of this piece in the playfield. In big mode,
+    /// each block of the piece occupies a 2 x 2 square.
+    pub fn get_cells(self) -> Vec<(i8, i8)> {
+        self.cells().collect()
+    }
+
+    /// Returns an iterator over the (row, col) position of each block of this piece in the
+    /// playfield. Unlike `get_cells`, this does not allocate.
+    pub fn cells(self) -> impl Iterator<Item = (i8, i8)> {
+        let bounding_box = self.get_bounding_box();
+        (0..4)
+            .flat_map(|row_offset| (0..4).map(move |col_offset| (row_offset, col_offset)))
+            .filter(move |&(row_offset, col_offset)| {
+                bounding_box[row_offset][col_offset] == Space::Block
+            })
+            .flat_map(move |(row_offset, col_offset)| {
+                let row = self.row + self.scale * row_offset as i8;
+                let col = self.col + self.scale * col_offset as i8;
+                let scale = self.scale;
+                (0..scale).flat_map(move |scale_row| {
+                    (0..scale).map(move |scale_col| (row + scale_row, col + scale_col))
+                })
+            })
+    }
+
+    /// Returns a copy of this piece dropped as far as possible on the specified playfield, using
+    /// normal downward gravity.
+    pub fn ghost_on(self, playfield: &Playfield) -> CurrentPiece {
+        self.dropped_on(playfield, -1, Playfield::TOTAL_HEIGHT as i8)
+    }
+
+    /// Returns a copy of this piece moved as far as possible on the specified playfield, one block
+    /// at a time in the specified row direction, without going above the specified ceiling row.
+    pub(crate) fn dropped_on(
+        self,
+        playfield: &Playfield,
+        row_offset: i8,
+        ceiling: i8,
+    ) -> CurrentPiece {
+        let mut ghost = self;
+        loop {
+            ghost.row += row_offset * ghost.scale;
+            if ghost.collides_on(playfield, ceiling) {
+                ghost.row -= row_offset * ghost.scale;
+                return ghost;
+            }
+        }
+    }
+
+    /// Returns whether any block of this piece is outside of the specified playfield, above the
+    /// specified ceiling row, or overlapping a block.
+    pub(crate) fn collides_on(self, playfield: &Playfield, ceiling: i8) -> bool {
+        self.cells()
+            .any(|(row, col)| row > ceiling || playfield.is_occupied(row, col))
+    }
+}
+
 impl fmt::Debug for Space {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -495,6 +1300,415 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_playfield_try_set_and_try_clear() {
+        let mut playfield = Playfield::new();
+        assert_eq!(playfield.try_set(1, 1), Result::Ok(()));
+        assert_eq!(playfield.get(1, 1), Space::Block);
+        assert_eq!(playfield.try_clear(1, 1), Result::Ok(()));
+        assert_eq!(playfield.get(1, 1), Space::Empty);
+
+        let out_of_bounds = [
+            (0, 1, PlayfieldError::RowOutOfBounds(0)),
+            (41, 1, PlayfieldError::RowOutOfBounds(41)),
+            (1, 0, PlayfieldError::ColOutOfBounds(0)),
+            (1, 11, PlayfieldError::ColOutOfBounds(11)),
+            // Rows are checked before columns.
+            (0, 0, PlayfieldError::RowOutOfBounds(0)),
+        ];
+        for (row, col, error) in out_of_bounds.iter() {
+            assert_eq!(playfield.try_set(*row, *col), Result::Err(*error));
+            assert_eq!(playfield.try_clear(*row, *col), Result::Err(*error));
+        }
+        assert_eq!(playfield.block_count(), 0);
+        assert_eq!(
+            PlayfieldError::ColOutOfBounds(11).to_string(),
+            "col 11 is not between 1 and 10."
+        );
+    }
+
+    #[test]
+    fn test_playfield_clone() {
+        let mut playfield = Playfield::new();
+        playfield.set_tetromino(1, 1, Tetromino::T);
+
+        let mut clone = playfield.clone();
+        assert_eq!(clone.get_tetromino(1, 1), Option::Some(Tetromino::T));
+
+        // Modifying the clone does not modify the original.
+        clone.clear(1, 1);
+        clone.set(2, 2);
+        assert_eq!(playfield.get_tetromino(1, 1), Option::Some(Tetromino::T));
+        assert_eq!(playfield.get(2, 2), Space::Empty);
+    }
+
+    #[test]
+    fn test_playfield_is_row_full() {
+        let mut playfield = Playfield::new();
+        assert!(!playfield.is_row_full(1));
+
+        for col in 1..=Playfield::WIDTH {
+            playfield.set(1, col);
+        }
+        assert!(playfield.is_row_full(1));
+
+        playfield.clear(1, 5);
+        assert!(!playfield.is_row_full(1));
+    }
+
+    #[test]
+    fn test_playfield_set_tetromino() {
+        let mut playfield = Playfield::new();
+        playfield.set_tetromino(1, 1, Tetromino::T);
+        assert_eq!(playfield.get(1, 1), Space::Block);
+        assert_eq!(playfield.get_tetromino(1, 1), Option::Some(Tetromino::T));
+
+        // Blocks set without a tetromino or cleared spaces have no tetromino.
+        playfield.set(1, 1);
+        assert_eq!(playfield.get_tetromino(1, 1), Option::None);
+        playfield.set_tetromino(1, 1, Tetromino::T);
+        playfield.clear(1, 1);
+        assert_eq!(playfield.get_tetromino(1, 1), Option::None);
+    }
+
+    #[test]
+    fn test_playfield_color_index() {
+        let shapes = [
+            Tetromino::I,
+            Tetromino::O,
+            Tetromino::T,
+            Tetromino::S,
+            Tetromino::Z,
+            Tetromino::J,
+            Tetromino::L,
+        ];
+        let mut playfield = Playfield::new();
+        for (col, shape) in shapes.iter().enumerate() {
+            playfield.set_tetromino(1, col as u8 + 1, *shape);
+        }
+        playfield.set(1, 8);
+
+        let mut indices = HashSet::new();
+        for col in 1..=8 {
+            indices.insert(playfield.color_index(1, col).unwrap());
+        }
+        assert_eq!(indices.len(), 8);
+
+        assert_eq!(playfield.color_index(1, 9), Option::None);
+        assert_eq!(playfield.color_index(2, 1), Option::None);
+    }
+
+    #[test]
+    fn test_playfield_highest_block_row() {
+        let mut playfield = Playfield::new();
+        assert_eq!(playfield.highest_block_row(), Option::None);
+
+        playfield.set(1, 1);
+        assert_eq!(playfield.highest_block_row(), Option::Some(1));
+        playfield.set(7, 10);
+        assert_eq!(playfield.highest_block_row(), Option::Some(7));
+        playfield.set(3, 5);
+        assert_eq!(playfield.highest_block_row(), Option::Some(7));
+    }
+
+    #[test]
+    fn test_playfield_add_garbage_row() {
+        let mut playfield = Playfield::new();
+        playfield.set_tetromino(1, 3, Tetromino::T);
+        playfield.add_garbage_row(4);
+
+        // Existing blocks move up.
+        assert_eq!(playfield.get_tetromino(2, 3), Option::Some(Tetromino::T));
+        assert_eq!(playfield.get(1, 3), Space::Block);
+        assert_eq!(playfield.get_tetromino(1, 3), Option::None);
+        for col in 1..=Playfield::WIDTH {
+            let expected = if col == 4 { Space::Empty } else { Space::Block };
+            assert_eq!(playfield.get(1, col), expected);
+        }
+        assert_eq!(playfield.block_count(), 10);
+    }
+
+    #[test]
+    fn test_playfield_subgrid() {
+        let mut playfield = Playfield::new();
+        playfield.set(1, 1);
+        playfield.set(2, 5);
+        playfield.set(4, 10);
+        playfield.set(5, 3);
+
+        let subgrid = playfield.subgrid(1, 4);
+        assert_eq!(subgrid.len(), 4);
+        for (i, row) in subgrid.iter().enumerate() {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(row[col as usize - 1], playfield.get(i as u8 + 1, col));
+            }
+        }
+        assert_eq!(subgrid[3][9], Space::Block);
+
+        assert_eq!(playfield.subgrid(5, 5)[0][2], Space::Block);
+        assert!(playfield.subgrid(5, 4).is_empty());
+    }
+
+    #[test]
+    fn test_playfield_union() {
+        let mut a = Playfield::new();
+        a.set_tetromino(1, 1, Tetromino::T);
+        let mut b = Playfield::new();
+        b.set_tetromino(40, 10, Tetromino::I);
+
+        let union = a.union(&b);
+        assert_eq!(union.block_count(), 2);
+        assert_eq!(union.get_tetromino(1, 1), Option::Some(Tetromino::T));
+        assert_eq!(union.get_tetromino(40, 10), Option::Some(Tetromino::I));
+        // The original playfields are unchanged.
+        assert_eq!(a.block_count(), 1);
+        assert_eq!(b.block_count(), 1);
+
+        // Overlapping blocks keep the tetromino from the first playfield.
+        b.set_tetromino(1, 1, Tetromino::L);
+        assert_eq!(a.union(&b).get_tetromino(1, 1), Option::Some(Tetromino::T));
+        assert_eq!(b.union(&a).get_tetromino(1, 1), Option::Some(Tetromino::L));
+        assert_eq!(a.union(&b).block_count(), 2);
+    }
+
+    #[test]
+    fn test_playfield_rotate_180() {
+        let mut playfield = Playfield::new();
+        // An L shape in the bottom-left corner.
+        playfield.set_tetromino(1, 1, Tetromino::L);
+        playfield.set_tetromino(1, 2, Tetromino::L);
+        playfield.set_tetromino(2, 1, Tetromino::L);
+        playfield.set_tetromino(3, 1, Tetromino::L);
+        playfield.set(5, 7);
+        playfield.set(21, 1);
+
+        playfield.rotate_180();
+        assert_eq!(playfield.block_count(), 6);
+        assert_eq!(playfield.get_tetromino(20, 10), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get_tetromino(20, 9), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get_tetromino(19, 10), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get_tetromino(18, 10), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get(16, 4), Space::Block);
+        assert_eq!(playfield.get_tetromino(16, 4), Option::None);
+        assert_eq!(playfield.get(1, 1), Space::Empty);
+        // Blocks above the visible playfield do not move.
+        assert_eq!(playfield.get(21, 1), Space::Block);
+
+        // Rotating twice restores the original playfield.
+        playfield.rotate_180();
+        assert_eq!(playfield.block_count(), 6);
+        assert_eq!(playfield.get_tetromino(1, 2), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get_tetromino(3, 1), Option::Some(Tetromino::L));
+        assert_eq!(playfield.get(5, 7), Space::Block);
+    }
+
+    #[test]
+    fn test_playfield_hole_count() {
+        let mut playfield = Playfield::new();
+        assert_eq!(playfield.hole_count(), 0);
+
+        // Blocks resting on the floor or other blocks do not create holes.
+        playfield.set(1, 1);
+        playfield.set(2, 1);
+        assert_eq!(playfield.hole_count(), 0);
+
+        playfield.set(4, 2);
+        assert_eq!(playfield.hole_count(), 3);
+        playfield.set(3, 3);
+        playfield.set(1, 3);
+        assert_eq!(playfield.hole_count(), 4);
+    }
+
+    #[test]
+    fn test_playfield_flat_surface_width() {
+        let mut playfield = Playfield::new();
+        assert_eq!(playfield.flat_surface_width(), 10);
+
+        // A perfectly flat stack.
+        for col in 1..=Playfield::WIDTH {
+            playfield.set(1, col);
+            playfield.set(2, col);
+        }
+        assert_eq!(playfield.flat_surface_width(), 10);
+
+        // Holes below the surface do not matter.
+        playfield.clear(1, 5);
+        assert_eq!(playfield.flat_surface_width(), 10);
+
+        // Jagged surface with heights 2 3 3 2 2 2 2 4 2 3.
+        playfield.set(3, 2);
+        playfield.set(3, 3);
+        playfield.set(4, 8);
+        playfield.set(3, 10);
+        assert_eq!(playfield.flat_surface_width(), 4);
+
+        // Alternating heights.
+        for col in (1..=Playfield::WIDTH).step_by(2) {
+            playfield.set(5, col);
+        }
+        assert_eq!(playfield.flat_surface_width(), 1);
+    }
+
+    #[test]
+    fn test_playfield_is_topped_out() {
+        let mut playfield = Playfield::new();
+        assert!(!playfield.is_topped_out());
+
+        // Fill every visible row except for one column.
+        for row in 1..=Playfield::VISIBLE_HEIGHT {
+            for col in 2..=Playfield::WIDTH {
+                playfield.set(row, col);
+            }
+        }
+        assert!(!playfield.is_topped_out());
+
+        // Blocking part of the spawn area still leaves room for some pieces.
+        playfield.set(22, 5);
+        playfield.set(22, 6);
+        assert!(!playfield.is_topped_out());
+
+        // Every piece has a block in the bottom row of the spawn area.
+        for col in 4..=7 {
+            playfield.set(21, col);
+        }
+        assert!(playfield.is_topped_out());
+    }
+
+    #[test]
+    fn test_playfield_find_tslots() {
+        let mut playfield = Playfield::new();
+        assert!(playfield.find_tslots().is_empty());
+
+        // T-spin double slot, with an overhang on the left.
+        // ---#------
+        // ###---####
+        // ####-#####
+        playfield.set(3, 4);
+        for col in 1..=Playfield::WIDTH {
+            if col < 4 || col > 6 {
+                playfield.set(2, col);
+            }
+            if col != 5 {
+                playfield.set(1, col);
+            }
+        }
+
+        let tslots: HashSet<(u8, u8, Rotation)> = playfield.find_tslots().into_iter().collect();
+        // A counter-clockwise T also fills the notch under the overhang, but only clears one row.
+        let expected: HashSet<(u8, u8, Rotation)> =
+            [(2, 5, Rotation::OneEighty)].iter().cloned().collect();
+        assert_eq!(tslots, expected);
+
+        // Without the overhang, the T can not fill a notch.
+        playfield.clear(3, 4);
+        assert!(playfield.find_tslots().is_empty());
+        playfield.set(3, 4);
+
+        // Covering the slot makes it inaccessible.
+        playfield.set(3, 5);
+        playfield.set(3, 6);
+        assert!(playfield.find_tslots().is_empty());
+    }
+
+    #[test]
+    fn test_playfield_find_classified_tslots() {
+        let mut playfield = Playfield::new();
+
+        // T-spin double slot, as in `test_playfield_find_tslots`.
+        playfield.set(3, 4);
+        for col in 1..=Playfield::WIDTH {
+            if !(4..=6).contains(&col) {
+                playfield.set(2, col);
+            }
+            if col != 5 {
+                playfield.set(1, col);
+            }
+        }
+        assert_eq!(
+            playfield.tslot_kind(2, 5, Rotation::OneEighty),
+            Option::Some(TSlotKind::Double)
+        );
+        assert_eq!(
+            playfield.tslot_kind(2, 5, Rotation::Spawn),
+            Option::Some(TSlotKind::Single)
+        );
+        assert_eq!(playfield.tslot_kind(10, 5, Rotation::Spawn), Option::None);
+
+        // T-spin triple tower. The notch in row 2 is covered by the overhang in row 3.
+        // ##-#######
+        // ##--######
+        // ##-#######
+        let mut playfield = Playfield::new();
+        for col in 1..=Playfield::WIDTH {
+            if col != 3 {
+                playfield.set(1, col);
+                playfield.set(3, col);
+            }
+            if !(3..=4).contains(&col) {
+                playfield.set(2, col);
+            }
+        }
+        // Walls above the slot.
+        playfield.set(4, 2);
+        playfield.set(4, 4);
+        playfield.set(5, 4);
+
+        let tslots = playfield.find_classified_tslots();
+        assert!(tslots.contains(&(2, 3, Rotation::Clockwise, TSlotKind::Triple)));
+        assert!(tslots
+            .iter()
+            .all(|&(_, _, rotation, kind)| kind != TSlotKind::Triple
+                || rotation == Rotation::Clockwise));
+
+        // Without a complete bottom row, only a double is possible.
+        playfield.clear(1, 10);
+        assert_eq!(
+            playfield.tslot_kind(2, 3, Rotation::Clockwise),
+            Option::Some(TSlotKind::Double)
+        );
+
+        // Blocks outside of the playfield are ignored.
+        let mut playfield = Playfield::new();
+        for col in 3..=Playfield::WIDTH {
+            playfield.set(1, col);
+        }
+        assert_eq!(
+            playfield.tslot_kind(1, 1, Rotation::OneEighty),
+            Option::Some(TSlotKind::Single)
+        );
+        assert_eq!(
+            playfield.tslot_kind(Playfield::TOTAL_HEIGHT, Playfield::WIDTH, Rotation::Spawn),
+            Option::None
+        );
+    }
+
+    #[test]
+    fn test_playfield_with_piece() {
+        let mut playfield = Playfield::new();
+        playfield.set(1, 1);
+
+        // O piece in spawn position occupies rows 21 and 22, columns 5 and 6.
+        let overlay = playfield.with_piece(CurrentPiece::new(Tetromino::O));
+        for &(row, col) in [(21, 5), (21, 6), (22, 5), (22, 6)].iter() {
+            assert_eq!(overlay.get(row, col), Space::Block);
+            assert_eq!(overlay.get_tetromino(row, col), Option::Some(Tetromino::O));
+            assert_eq!(playfield.get(row, col), Space::Empty);
+        }
+        assert_eq!(overlay.get(1, 1), Space::Block);
+    }
+
+    #[test]
+    fn test_tetromino_char() {
+        for c in "IOTSZJL".chars() {
+            let shape = Tetromino::from_char(c).unwrap();
+            assert_eq!(shape.to_char(), c);
+        }
+        assert_eq!(Tetromino::from_char('T'), Option::Some(Tetromino::T));
+        assert_eq!(Tetromino::from_char('t'), Option::None);
+        assert_eq!(Tetromino::from_char('X'), Option::None);
+        assert_eq!(Tetromino::from_char('#'), Option::None);
+    }
+
     #[test]
     fn test_rotation_cw() {
         let r = Rotation::Spawn;
@@ -602,6 +1816,17 @@ mod tests {
         assert_eq!(l_bbs.len(), 4);
     }
 
+    #[test]
+    fn test_piece_tight_bounds() {
+        let mut piece = Piece::new(Tetromino::I);
+        assert_eq!(piece.tight_bounds(), (2, 2, 0, 3));
+        piece.rotate_cw();
+        assert_eq!(piece.tight_bounds(), (0, 3, 2, 2));
+
+        assert_eq!(Piece::new(Tetromino::O).tight_bounds(), (2, 3, 1, 2));
+        assert_eq!(Piece::new(Tetromino::T).tight_bounds(), (2, 3, 0, 2));
+    }
+
     fn assert_piece_bounding_box(piece: &mut Piece) -> HashSet<[[Space; 4]; 4]> {
         let mut bounding_boxes = HashSet::new();
         // Test all 4 rotations
@@ -624,4 +1849,90 @@ mod tests {
 
         bounding_boxes
     }
+
+    #[test]
+    fn test_full_rows() {
+        let mut playfield = Playfield::new();
+        assert!(playfield.full_rows().is_empty());
+
+        for col in 1..=Playfield::WIDTH {
+            playfield.set(1, col);
+            playfield.set(4, col);
+        }
+        for col in 1..Playfield::WIDTH {
+            playfield.set(2, col);
+        }
+        assert_eq!(playfield.full_rows(), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_apply_column_gravity() {
+        let mut playfield = Playfield::new();
+        playfield.set_tetromino(3, 1, Tetromino::T);
+        playfield.set(5, 1);
+        playfield.set(1, 2);
+        playfield.set(2, 2);
+        playfield.set(40, 10);
+
+        playfield.apply_column_gravity();
+        assert_eq!(playfield.get(1, 1), Space::Block);
+        assert_eq!(playfield.get_tetromino(1, 1), Option::Some(Tetromino::T));
+        assert_eq!(playfield.get(2, 1), Space::Block);
+        assert_eq!(playfield.get_tetromino(2, 1), Option::None);
+        assert_eq!(playfield.get(1, 2), Space::Block);
+        assert_eq!(playfield.get(2, 2), Space::Block);
+        assert_eq!(playfield.get(1, 10), Space::Block);
+        assert_eq!(playfield.block_count(), 5);
+    }
+
+    #[test]
+    fn test_code_round_trip() {
+        assert_eq!(Playfield::new().to_code(), "");
+        assert_eq!(Playfield::from_code("").unwrap().block_count(), 0);
+
+        let mut playfield = Playfield::new();
+        playfield.set(1, 1);
+        assert_eq!(playfield.to_code(), "AAE");
+
+        for col in 1..Playfield::WIDTH {
+            playfield.set(2, col);
+        }
+        playfield.set_tetromino(3, 1, Tetromino::T);
+        playfield.set(4, 10);
+        playfield.set(40, 5);
+
+        let decoded = Playfield::from_code(&playfield.to_code()).unwrap();
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(decoded.get(row, col), playfield.get(row, col));
+            }
+        }
+        assert_eq!(decoded.to_code(), playfield.to_code());
+    }
+
+    #[test]
+    fn test_code_errors() {
+        assert_eq!(
+            Playfield::from_code("AB*D").unwrap_err(),
+            DecodeError::InvalidCharacter('*')
+        );
+        assert_eq!(
+            Playfield::from_code("AAAAA").unwrap_err(),
+            DecodeError::InvalidLength
+        );
+        // One byte is not a whole row.
+        assert_eq!(
+            Playfield::from_code("AA").unwrap_err(),
+            DecodeError::InvalidLength
+        );
+        assert_eq!(
+            Playfield::from_code(&"A".repeat(112)).unwrap_err(),
+            DecodeError::TooManyRows
+        );
+        // Row 2 sets bit 10, i.e. column 11.
+        assert_eq!(
+            Playfield::from_code("AAAEAA").unwrap_err(),
+            DecodeError::InvalidRow(2)
+        );
+    }
 }
@@ -1,3 +1,4 @@
 pub mod base;
 pub mod core;
 pub mod single;
+pub mod versus;
@@ -1,6 +1,8 @@
-use super::base::{BaseEngine, BaseEngineObserver, CurrentPiece, Engine, Gravity, State, TSpin};
-use super::core::{Playfield, Tetromino};
+use super::base::{Action, BaseEngine, BaseEngineObserver, Engine, Gravity, State, TSpin};
+use super::core::{CurrentPiece, Playfield, Tetromino};
+use super::versus;
 use std::cell::*;
+use std::fmt;
 use std::rc::Rc;
 
 const GRAVITY: [Gravity; 15] = [
@@ -24,6 +26,7 @@ const GRAVITY: [Gravity; 15] = [
 pub struct SinglePlayerEngine {
     base_engine: BaseEngine,
     stat_tracker: Rc<StatTracker>,
+    gravity_curve: Vec<Gravity>,
 }
 
 impl Engine for SinglePlayerEngine {
@@ -31,14 +34,13 @@ impl Engine for SinglePlayerEngine {
         let state = self.base_engine.tick();
 
         if let State::Spawn = state {
-            self.base_engine
-                .set_gravity(GRAVITY[self.stat_tracker.get_level() as usize - 1]);
+            self.base_engine.set_gravity(self.get_gravity());
         }
 
         state
     }
 
-    fn get_playfield(&self) -> Playfield {
+    fn get_playfield(&self) -> &Playfield {
         self.base_engine.get_playfield()
     }
 
@@ -46,6 +48,10 @@ impl Engine for SinglePlayerEngine {
         self.base_engine.get_current_piece()
     }
 
+    fn get_active_pieces(&self) -> (CurrentPiece, CurrentPiece) {
+        self.base_engine.get_active_pieces()
+    }
+
     fn get_hold_piece(&self) -> Option<Tetromino> {
         self.base_engine.get_hold_piece()
     }
@@ -54,6 +60,10 @@ impl Engine for SinglePlayerEngine {
         self.base_engine.get_next_pieces()
     }
 
+    fn pending_garbage(&self) -> u8 {
+        self.base_engine.pending_garbage()
+    }
+
     fn input_move_left(&self) {
         self.base_engine.input_move_left();
     }
@@ -81,25 +91,140 @@ impl Engine for SinglePlayerEngine {
     fn input_hold(&self) {
         self.base_engine.input_hold();
     }
+
+    fn input_snap_left(&self) {
+        self.base_engine.input_snap_left();
+    }
+
+    fn input_snap_right(&self) {
+        self.base_engine.input_snap_right();
+    }
+
+    fn submit_inputs(&self, actions: &[Action]) {
+        self.base_engine.submit_inputs(actions);
+    }
 }
 
 impl SinglePlayerEngine {
     pub fn new() -> SinglePlayerEngine {
+        SinglePlayerEngine::with_gravity_curve(GRAVITY.to_vec())
+    }
+
+    /// Creates a new engine using the specified gravity for each level, starting at level 1. The
+    /// maximum level is the length of the curve.
+    pub fn with_gravity_curve(gravity_curve: Vec<Gravity>) -> SinglePlayerEngine {
+        assert!(
+            !gravity_curve.is_empty(),
+            "gravity curve must not be empty."
+        );
+        assert!(
+            gravity_curve.len() <= u8::MAX as usize,
+            "gravity curve must not be longer than 255 levels."
+        );
+
         let mut base_engine = BaseEngine::new();
-        base_engine.set_gravity(GRAVITY[0]);
-        let stat_tracker = Rc::new(StatTracker::new());
+        base_engine.set_gravity(gravity_curve[0]);
+        let stat_tracker = Rc::new(StatTracker::new(gravity_curve.len() as u8));
 
         base_engine.add_observer(stat_tracker.clone());
+        // The first piece is already in play, so it does not spawn through the engine.
+        stat_tracker.on_spawn(*base_engine.get_current_piece().get_piece().get_shape());
 
         SinglePlayerEngine {
             base_engine,
             stat_tracker,
+            gravity_curve,
         }
     }
 
+    /// Creates a new engine whose gravity at each level is computed by the specified function. The
+    /// function is called with each level from 1 to 255, which is the maximum level.
+    pub fn with_gravity_fn(gravity_fn: fn(level: u8) -> Gravity) -> SinglePlayerEngine {
+        SinglePlayerEngine::with_gravity_curve((1..=u8::MAX).map(gravity_fn).collect())
+    }
+
+    /// Returns the gravity for each level, starting at level 1.
+    pub fn get_gravity_curve(&self) -> &[Gravity] {
+        &self.gravity_curve
+    }
+
+    /// Returns the gravity for the current level.
+    fn get_gravity(&self) -> Gravity {
+        self.gravity_curve[self.stat_tracker.get_level() as usize - 1]
+    }
+
     fn get_score(&self) -> u32 {
         self.stat_tracker.score.get()
     }
+
+    /// Adds an observer which is notified of engine events, including level ups.
+    pub fn add_observer(&mut self, observer: Rc<dyn BaseEngineObserver>) {
+        self.base_engine.add_observer(observer.clone());
+        self.stat_tracker.observers.borrow_mut().push(observer);
+    }
+
+    /// Resets the score, combo, level and all other statistics, without changing the playfield or
+    /// any pieces.
+    pub fn reset_stats(&mut self) {
+        self.stat_tracker.reset();
+        self.base_engine.set_gravity(self.get_gravity());
+    }
+
+    /// Sets the maximum number of ticks between line clears for a combo to continue. If more ticks
+    /// pass, the next line clear starts a new combo.
+    pub fn set_combo_timeout(&mut self, ticks: u32) {
+        self.stat_tracker.combo_timeout.set(Option::Some(ticks));
+    }
+
+    /// Returns the highest the stack has reached during this game.
+    pub fn get_max_stack_height(&self) -> u8 {
+        self.stat_tracker.get_max_stack_height()
+    }
+
+    /// Returns the number of each tetromino which has spawned, indexed by `Tetromino::index`.
+    pub fn get_piece_counts(&self) -> [u32; 7] {
+        self.stat_tracker.get_piece_counts()
+    }
+
+    /// Returns the number of times each type of line clear has been performed during this game.
+    pub fn get_clear_type_counts(&self) -> ClearCounts {
+        self.stat_tracker.get_clear_type_counts()
+    }
+
+    /// Returns the average number of inputs applied for each piece locked, or 0 if no pieces have
+    /// been locked.
+    pub fn get_inputs_per_piece(&self) -> f64 {
+        self.stat_tracker.get_inputs_per_piece()
+    }
+
+    /// Returns the number of lines which must be cleared to reach the next level, or 0 if already
+    /// at the maximum level.
+    pub fn lines_to_next_level(&self) -> u32 {
+        self.stat_tracker.lines_to_next_level()
+    }
+}
+
+impl fmt::Debug for SinglePlayerEngine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?}",
+            self.get_playfield().with_piece(self.get_current_piece())
+        )
+    }
+}
+
+/// The number of times each type of line clear was performed.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ClearCounts {
+    pub singles: u32,
+    pub doubles: u32,
+    pub triples: u32,
+    pub tetrises: u32,
+    pub t_spin_minis: u32,
+    pub t_spin_singles: u32,
+    pub t_spin_doubles: u32,
+    pub t_spin_triples: u32,
 }
 
 struct StatTracker {
@@ -108,7 +233,21 @@ struct StatTracker {
     lines_cleared: Cell<u32>,
     combo_status: Cell<ComboStatus>,
     current_combo: Cell<u8>,
+    combo_timeout: Cell<Option<u32>>,
+    ticks: Cell<u32>,
+    last_line_clear_tick: Cell<u32>,
     back_to_back: Cell<bool>,
+    max_stack_height: Cell<u8>,
+    total_inputs: Cell<u32>,
+    pieces_locked: Cell<u32>,
+    piece_counts: Cell<[u32; 7]>,
+    // Whether the engine has a hold piece, and whether the next spawn is a piece swapped back in
+    // from hold, which was already counted.
+    has_hold_piece: Cell<bool>,
+    spawn_from_hold: Cell<bool>,
+    clear_counts: Cell<ClearCounts>,
+    max_level: u8,
+    observers: RefCell<Vec<Rc<dyn BaseEngineObserver>>>,
 }
 
 #[derive(Copy, Clone)]
@@ -123,25 +262,95 @@ enum ComboStatus {
 }
 
 impl StatTracker {
-    fn new() -> StatTracker {
+    fn new(max_level: u8) -> StatTracker {
         StatTracker {
             score: Cell::new(0),
             last_lock: Cell::new(TSpin::None),
             lines_cleared: Cell::new(0),
             combo_status: Cell::new(ComboStatus::Inactive),
             current_combo: Cell::new(0),
+            combo_timeout: Cell::new(Option::None),
+            ticks: Cell::new(0),
+            last_line_clear_tick: Cell::new(0),
             back_to_back: Cell::new(false),
+            max_stack_height: Cell::new(0),
+            total_inputs: Cell::new(0),
+            pieces_locked: Cell::new(0),
+            piece_counts: Cell::new([0; 7]),
+            has_hold_piece: Cell::new(false),
+            spawn_from_hold: Cell::new(false),
+            clear_counts: Cell::new(ClearCounts::default()),
+            max_level,
+            observers: RefCell::new(vec![]),
         }
     }
 
+    /// Resets all statistics to their initial values.
+    fn reset(&self) {
+        self.score.set(0);
+        self.last_lock.set(TSpin::None);
+        self.lines_cleared.set(0);
+        self.combo_status.set(ComboStatus::Inactive);
+        self.current_combo.set(0);
+        self.last_line_clear_tick.set(self.ticks.get());
+        self.back_to_back.set(false);
+        self.max_stack_height.set(0);
+        self.total_inputs.set(0);
+        self.pieces_locked.set(0);
+        self.piece_counts.set([0; 7]);
+        self.clear_counts.set(ClearCounts::default());
+    }
+
     fn get_level(&self) -> u8 {
         let level = 1 + self.lines_cleared.get() / 10;
-        std::cmp::min(level, 15) as u8
+        std::cmp::min(level, u32::from(self.max_level)) as u8
+    }
+
+    fn get_max_stack_height(&self) -> u8 {
+        self.max_stack_height.get()
+    }
+
+    fn get_piece_counts(&self) -> [u32; 7] {
+        self.piece_counts.get()
+    }
+
+    fn get_clear_type_counts(&self) -> ClearCounts {
+        self.clear_counts.get()
+    }
+
+    fn get_inputs_per_piece(&self) -> f64 {
+        match self.pieces_locked.get() {
+            0 => 0.,
+            pieces_locked => f64::from(self.total_inputs.get()) / f64::from(pieces_locked),
+        }
+    }
+
+    fn lines_to_next_level(&self) -> u32 {
+        if self.get_level() == self.max_level {
+            0
+        }
+        else {
+            10 - self.lines_cleared.get() % 10
+        }
     }
 }
 
 impl BaseEngineObserver for StatTracker {
-    fn on_lock(&self, t_spin: TSpin) {
+    fn on_spawn(&self, shape: Tetromino) {
+        if self.spawn_from_hold.replace(false) {
+            return;
+        }
+        let mut piece_counts = self.piece_counts.get();
+        piece_counts[shape.index() as usize] += 1;
+        self.piece_counts.set(piece_counts);
+    }
+
+    fn on_hold(&self, _swapped_out: Tetromino, _swapped_in: Tetromino) {
+        // The first hold swaps in a new piece from the next queue.
+        self.spawn_from_hold.set(self.has_hold_piece.replace(true));
+    }
+
+    fn on_lock(&self, t_spin: TSpin, playfield: &Playfield) {
         // Downgrade combo status. It should be reset to Active on line clear.
         let combo_status = match self.combo_status.get() {
             ComboStatus::Active => ComboStatus::Maybe,
@@ -153,6 +362,16 @@ impl BaseEngineObserver for StatTracker {
         self.combo_status.set(combo_status);
 
         self.last_lock.set(t_spin);
+        self.pieces_locked.set(self.pieces_locked.get() + 1);
+
+        let stack_height = playfield.highest_block_row().unwrap_or(0);
+        if stack_height > self.max_stack_height.get() {
+            self.max_stack_height.set(stack_height);
+        }
+    }
+
+    fn on_action(&self, _action: Action) {
+        self.total_inputs.set(self.total_inputs.get() + 1);
     }
 
     fn on_soft_drop(&self, n_rows: u8) {
@@ -163,22 +382,60 @@ impl BaseEngineObserver for StatTracker {
         self.score.set(self.score.get() + 2 * u32::from(n_rows));
     }
 
+    fn on_tick(&self, _playfield: &Playfield, _current_piece: CurrentPiece) {
+        self.ticks.set(self.ticks.get() + 1);
+    }
+
     fn on_line_clear(&self, n_rows: u8) {
+        // Big pieces and cascades can clear more than four rows at once. Score these as separate
+        // clears of at most four rows.
+        if n_rows > 4 {
+            self.on_line_clear(4);
+            self.on_line_clear(n_rows - 4);
+            return;
+        }
+
+        // The combo ends if too much time has passed since the last line clear.
+        if let Option::Some(timeout) = self.combo_timeout.get() {
+            if self.ticks.get() - self.last_line_clear_tick.get() > timeout {
+                self.current_combo.set(0);
+            }
+        }
+        self.last_line_clear_tick.set(self.ticks.get());
+
         // Increment combo
         self.combo_status.set(ComboStatus::Active);
         self.current_combo.set(self.current_combo.get() + 1);
 
-        let (mut points, back_to_back) = match (n_rows, self.last_lock.get()) {
-            (1, TSpin::None) => (100, false),
-            (2, TSpin::None) => (300, false),
-            (3, TSpin::None) => (500, false),
-            (4, TSpin::None) => (800, true),
-            (1, TSpin::Mini) => (200, true),
-            (1, TSpin::Regular) => (800, true),
-            (2, TSpin::Regular) => (1200, true),
-            (3, TSpin::Regular) => (1600, true),
+        // Further clears before the next lock are cascades, which are never T-spins.
+        let t_spin = self.last_lock.replace(TSpin::None);
+        let mut points = match (n_rows, t_spin) {
+            (1, TSpin::None) => 100,
+            (2, TSpin::None) => 300,
+            (3, TSpin::None) => 500,
+            (4, TSpin::None) => 800,
+            (1, TSpin::Mini) => 200,
+            (1, TSpin::Regular) => 800,
+            (2, TSpin::Regular) => 1200,
+            (3, TSpin::Regular) => 1600,
+            (_, _) => panic!("This should be impossible."),
+        };
+
+        let mut clear_counts = self.clear_counts.get();
+        let count = match (n_rows, t_spin) {
+            (1, TSpin::None) => &mut clear_counts.singles,
+            (2, TSpin::None) => &mut clear_counts.doubles,
+            (3, TSpin::None) => &mut clear_counts.triples,
+            (4, TSpin::None) => &mut clear_counts.tetrises,
+            (_, TSpin::Mini) => &mut clear_counts.t_spin_minis,
+            (1, TSpin::Regular) => &mut clear_counts.t_spin_singles,
+            (2, TSpin::Regular) => &mut clear_counts.t_spin_doubles,
+            (_, TSpin::Regular) => &mut clear_counts.t_spin_triples,
             (_, _) => panic!("This should be impossible."),
         };
+        *count += 1;
+        self.clear_counts.set(clear_counts);
+        let back_to_back = versus::is_difficult_clear(n_rows, t_spin);
 
         // If we were already in the middle of a back-to-back,
         // and the current line clear is also a back-to-back ...
@@ -197,6 +454,356 @@ impl BaseEngineObserver for StatTracker {
 
         // Do not update lines cleared until after final score is computed so that level is based on
         // lines cleared before this current action.
+        let level = self.get_level();
         self.lines_cleared.set(self.lines_cleared.get() + u32::from(n_rows));
+
+        let new_level = self.get_level();
+        if new_level > level {
+            for observer in self.observers.borrow().iter() {
+                observer.on_level_up(new_level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_player_engine_debug() {
+        let engine = SinglePlayerEngine::new();
+        assert_eq!(format!("{:?}", engine), format!("{:?}", engine.base_engine));
+    }
+
+    #[test]
+    fn test_stat_tracker_max_stack_height() {
+        let stat_tracker = StatTracker::new(15);
+        assert_eq!(stat_tracker.get_max_stack_height(), 0);
+
+        let mut playfield = Playfield::new();
+        for row in 1..=5 {
+            playfield.set(row, 1);
+        }
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        assert_eq!(stat_tracker.get_max_stack_height(), 5);
+
+        // Simulate line clears lowering the stack.
+        playfield.clear(5, 1);
+        playfield.clear(4, 1);
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        assert_eq!(stat_tracker.get_max_stack_height(), 5);
+
+        for row in 1..=8 {
+            playfield.set(row, 2);
+        }
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        assert_eq!(stat_tracker.get_max_stack_height(), 8);
+    }
+
+    #[test]
+    fn test_level_cap_follows_gravity_curve() {
+        let engine = SinglePlayerEngine::new();
+        for _ in 0..50 {
+            engine.stat_tracker.on_line_clear(4);
+        }
+        assert_eq!(engine.stat_tracker.get_level(), 15);
+
+        let engine = SinglePlayerEngine::with_gravity_curve(vec![Gravity::TicksPerRow(1); 20]);
+        for level in 1..=20 {
+            assert_eq!(engine.stat_tracker.get_level(), level);
+            for _ in 0..10 {
+                engine.stat_tracker.on_line_clear(1);
+            }
+        }
+        assert_eq!(engine.stat_tracker.get_level(), 20);
+    }
+
+    #[test]
+    fn test_get_gravity_curve() {
+        let engine = SinglePlayerEngine::new();
+        assert_eq!(engine.get_gravity_curve().len(), 15);
+        assert_eq!(engine.get_gravity_curve()[0], Gravity::TicksPerRow(60));
+
+        let curve = vec![Gravity::TicksPerRow(10), Gravity::RowsPerTick(1)];
+        let engine = SinglePlayerEngine::with_gravity_curve(curve.clone());
+        assert_eq!(engine.get_gravity_curve(), &curve[..]);
+    }
+
+    #[test]
+    fn test_with_gravity_fn() {
+        fn gravity(level: u8) -> Gravity {
+            // Exponential curve: double the speed every 3 levels.
+            let ticks_per_row = 64. / 2f64.powf(f64::from(level - 1) / 3.);
+            if ticks_per_row >= 1. {
+                Gravity::TicksPerRow(ticks_per_row.round() as u8)
+            }
+            else {
+                Gravity::RowsPerTick((1. / ticks_per_row).round() as u8)
+            }
+        }
+
+        let engine = SinglePlayerEngine::with_gravity_fn(gravity);
+        assert_eq!(engine.get_gravity(), Gravity::TicksPerRow(64));
+        for level in 2..=20 {
+            for _ in 0..10 {
+                engine.stat_tracker.on_line_clear(1);
+            }
+            assert_eq!(engine.stat_tracker.get_level(), level);
+            assert_eq!(engine.get_gravity(), gravity(level));
+        }
+        assert_eq!(engine.get_gravity(), Gravity::RowsPerTick(1));
+    }
+
+    #[test]
+    fn test_lines_to_next_level() {
+        let engine = SinglePlayerEngine::new();
+        assert_eq!(engine.lines_to_next_level(), 10);
+
+        engine.stat_tracker.on_line_clear(4);
+        engine.stat_tracker.on_line_clear(3);
+        assert_eq!(engine.lines_to_next_level(), 3);
+
+        engine.stat_tracker.on_line_clear(2);
+        assert_eq!(engine.lines_to_next_level(), 1);
+
+        engine.stat_tracker.on_line_clear(1);
+        assert_eq!(engine.stat_tracker.get_level(), 2);
+        assert_eq!(engine.lines_to_next_level(), 10);
+
+        // No more levels after the end of the gravity curve.
+        let engine = SinglePlayerEngine::with_gravity_curve(vec![Gravity::TicksPerRow(1); 2]);
+        engine.stat_tracker.on_line_clear(4);
+        engine.stat_tracker.on_line_clear(4);
+        engine.stat_tracker.on_line_clear(2);
+        assert_eq!(engine.stat_tracker.get_level(), 2);
+        assert_eq!(engine.lines_to_next_level(), 0);
+    }
+
+    #[test]
+    fn test_level_up_notifies_observers() {
+        struct LevelUpObserver {
+            levels: RefCell<Vec<u8>>,
+        }
+
+        impl BaseEngineObserver for LevelUpObserver {
+            fn on_level_up(&self, new_level: u8) {
+                self.levels.borrow_mut().push(new_level);
+            }
+        }
+
+        let mut engine = SinglePlayerEngine::with_gravity_curve(vec![Gravity::TicksPerRow(1); 3]);
+        let observer = Rc::new(LevelUpObserver {
+            levels: RefCell::new(vec![]),
+        });
+        engine.add_observer(observer.clone());
+
+        for _ in 0..9 {
+            engine.stat_tracker.on_line_clear(1);
+        }
+        assert!(observer.levels.borrow().is_empty());
+        engine.stat_tracker.on_line_clear(1);
+        assert_eq!(*observer.levels.borrow(), vec![2]);
+
+        // Clearing past the maximum level does not notify observers.
+        for _ in 0..10 {
+            engine.stat_tracker.on_line_clear(4);
+        }
+        assert_eq!(*observer.levels.borrow(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_inputs_per_piece() {
+        /// Presses an input for one tick, then releases it for one tick.
+        fn press(engine: &mut SinglePlayerEngine, input: fn(&SinglePlayerEngine)) {
+            input(engine);
+            engine.tick();
+            engine.tick();
+        }
+
+        let mut engine = SinglePlayerEngine::new();
+        engine.tick();
+        assert_eq!(engine.get_inputs_per_piece(), 0.);
+
+        press(&mut engine, SinglePlayerEngine::input_move_left);
+        press(&mut engine, SinglePlayerEngine::input_hard_drop);
+        assert_eq!(engine.get_inputs_per_piece(), 2.);
+
+        press(&mut engine, SinglePlayerEngine::input_hard_drop);
+        assert_eq!(engine.get_inputs_per_piece(), 1.5);
+
+        press(&mut engine, SinglePlayerEngine::input_rotate_cw);
+        press(&mut engine, SinglePlayerEngine::input_move_right);
+        press(&mut engine, SinglePlayerEngine::input_move_right);
+        press(&mut engine, SinglePlayerEngine::input_hard_drop);
+        assert_eq!(engine.get_inputs_per_piece(), 7. / 3.);
+    }
+
+    #[test]
+    fn test_piece_counts() {
+        let mut engine = BaseEngine::with_sequence(&[Tetromino::T]);
+        let stat_tracker = Rc::new(StatTracker::new(15));
+        engine.add_observer(stat_tracker.clone());
+        assert_eq!(stat_tracker.get_piece_counts(), [0; 7]);
+
+        for _ in 0..3 {
+            engine.input_hard_drop();
+            engine.tick();
+            engine.tick();
+        }
+
+        let t = Tetromino::T.index() as usize;
+        for (index, count) in stat_tracker.get_piece_counts().iter().enumerate() {
+            assert_eq!(*count, if index == t { 3 } else { 0 });
+        }
+
+        // Pieces swapped back in from hold are not counted again.
+        let mut engine = BaseEngine::with_sequence(&[Tetromino::T, Tetromino::I, Tetromino::O]);
+        let stat_tracker = Rc::new(StatTracker::new(15));
+        engine.add_observer(stat_tracker.clone());
+        engine.input_hold();
+        engine.tick();
+        engine.tick();
+        engine.input_hard_drop();
+        engine.tick();
+        engine.tick();
+        engine.input_hold();
+        engine.tick();
+        engine.tick();
+        assert_eq!(
+            engine.get_current_piece().get_piece().get_shape(),
+            &Tetromino::T
+        );
+        // The first T spawned before the stat tracker was added.
+        let mut expected = [0; 7];
+        for shape in [Tetromino::I, Tetromino::O].iter() {
+            expected[shape.index() as usize] = 1;
+        }
+        assert_eq!(stat_tracker.get_piece_counts(), expected);
+
+        // The first piece is counted when the engine is created.
+        let engine = SinglePlayerEngine::new();
+        assert_eq!(engine.get_piece_counts().iter().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn test_reset_stats() {
+        let mut engine = SinglePlayerEngine::new();
+        engine.input_hard_drop();
+        engine.tick();
+        engine.tick();
+        for _ in 0..12 {
+            engine.stat_tracker.on_line_clear(1);
+        }
+        assert!(engine.get_score() > 0);
+        assert_eq!(engine.stat_tracker.get_level(), 2);
+
+        let playfield = engine.get_playfield().clone();
+        let current_piece = engine.get_current_piece();
+        let next_pieces = engine.get_next_pieces();
+        engine.reset_stats();
+
+        assert_eq!(engine.get_score(), 0);
+        assert_eq!(engine.stat_tracker.current_combo.get(), 0);
+        assert_eq!(engine.stat_tracker.get_level(), 1);
+        assert_eq!(engine.get_max_stack_height(), 0);
+        assert_eq!(engine.get_piece_counts(), [0; 7]);
+        assert_eq!(engine.get_gravity(), GRAVITY[0]);
+
+        // The board is unchanged.
+        assert!(playfield.block_count() > 0);
+        for row in 1..=Playfield::TOTAL_HEIGHT {
+            for col in 1..=Playfield::WIDTH {
+                assert_eq!(
+                    engine.get_playfield().get(row, col),
+                    playfield.get(row, col)
+                );
+            }
+        }
+        assert_eq!(engine.get_current_piece(), current_piece);
+        assert_eq!(engine.get_next_pieces(), next_pieces);
+    }
+
+    #[test]
+    fn test_clear_type_counts() {
+        let engine = SinglePlayerEngine::new();
+        let stat_tracker = &engine.stat_tracker;
+        assert_eq!(engine.get_clear_type_counts(), ClearCounts::default());
+
+        let playfield = Playfield::new();
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        stat_tracker.on_line_clear(4);
+        stat_tracker.on_lock(TSpin::Regular, &playfield);
+        stat_tracker.on_line_clear(2);
+        assert_eq!(
+            engine.get_clear_type_counts(),
+            ClearCounts {
+                tetrises: 1,
+                t_spin_doubles: 1,
+                ..ClearCounts::default()
+            }
+        );
+
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        stat_tracker.on_line_clear(1);
+        stat_tracker.on_lock(TSpin::Mini, &playfield);
+        stat_tracker.on_line_clear(1);
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        stat_tracker.on_line_clear(4);
+        let counts = engine.get_clear_type_counts();
+        assert_eq!(counts.tetrises, 2);
+        assert_eq!(counts.singles, 1);
+        assert_eq!(counts.t_spin_minis, 1);
+        assert_eq!(counts.t_spin_singles, 0);
+
+        // Clears of more than four rows count as a tetris followed by the remaining rows.
+        stat_tracker.on_lock(TSpin::None, &playfield);
+        stat_tracker.on_line_clear(6);
+        let counts = engine.get_clear_type_counts();
+        assert_eq!(counts.tetrises, 3);
+        assert_eq!(counts.doubles, 1);
+    }
+
+    #[test]
+    fn test_combo_timeout() {
+        let mut engine = SinglePlayerEngine::new();
+        engine.set_combo_timeout(10);
+        let stat_tracker = engine.stat_tracker.clone();
+        let playfield = Playfield::new();
+        let tick = |n| {
+            for _ in 0..n {
+                stat_tracker.on_tick(&playfield, engine.get_current_piece());
+            }
+        };
+        let clear = || {
+            stat_tracker.on_lock(TSpin::None, &playfield);
+            stat_tracker.on_line_clear(1);
+            stat_tracker.current_combo.get()
+        };
+
+        assert_eq!(clear(), 1);
+        tick(10);
+        assert_eq!(clear(), 2);
+        tick(5);
+        assert_eq!(clear(), 3);
+
+        // The timeout elapses, so the next clear starts a new combo.
+        tick(11);
+        assert_eq!(clear(), 1);
+        tick(1);
+        assert_eq!(clear(), 2);
+    }
+
+    #[test]
+    fn test_cascade_clears_are_not_t_spins() {
+        let engine = SinglePlayerEngine::new();
+        let stat_tracker = engine.stat_tracker.clone();
+        stat_tracker.on_lock(TSpin::Mini, &Playfield::new());
+        stat_tracker.on_line_clear(1);
+        stat_tracker.on_line_clear(2);
+
+        let clear_counts = engine.get_clear_type_counts();
+        assert_eq!(clear_counts.t_spin_minis, 1);
+        assert_eq!(clear_counts.doubles, 1);
     }
 }
@@ -0,0 +1,46 @@
+use super::base::TSpin;
+
+pub use super::base::{GarbagePattern, GarbageTiming};
+
+/// Returns whether clearing the specified number of rows with the specified t-spin is a difficult
+/// clear. Consecutive difficult clears are back-to-back. Tetrises and any t-spin which clears at
+/// least one row are difficult.
+pub fn is_difficult_clear(n_rows: u8, t_spin: TSpin) -> bool {
+    match (n_rows, t_spin) {
+        (0, _) => false,
+        (4, _) => true,
+        (_, TSpin::None) => false,
+        (_, _) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_difficult_clear() {
+        let cases = [
+            (0, TSpin::None, false),
+            (1, TSpin::None, false),
+            (2, TSpin::None, false),
+            (3, TSpin::None, false),
+            (4, TSpin::None, true),
+            (0, TSpin::Mini, false),
+            (1, TSpin::Mini, true),
+            (0, TSpin::Regular, false),
+            (1, TSpin::Regular, true),
+            (2, TSpin::Regular, true),
+            (3, TSpin::Regular, true),
+        ];
+        for (n_rows, t_spin, expected) in cases.iter() {
+            assert_eq!(
+                is_difficult_clear(*n_rows, *t_spin),
+                *expected,
+                "{} rows with {:?}",
+                n_rows,
+                t_spin
+            );
+        }
+    }
+}
@@ -2,6 +2,7 @@ mod engine;
 mod render;
 
 use std::collections::HashSet;
+use std::time::Instant;
 
 use piston::input::{Button, ButtonArgs, ButtonState, Event, Input, Key, Loop};
 
@@ -9,30 +10,46 @@ use self::engine::{
     base::{Engine, State},
     single::SinglePlayerEngine,
 };
-use self::render::PistonRender;
+use self::render::{clock::GameClock, PistonRender, RenderOptions};
 
 fn main() {
     let mut engine = SinglePlayerEngine::new();
-    let mut window = engine.create_window();
+    let mut options = RenderOptions::default();
+    let mut window = engine.create_window_with_options(&options);
     let mut pressed_keys = HashSet::new();
+    // Update the engine at a fixed rate, independently of how often frames are rendered.
+    let mut clock = GameClock::new(60);
+    let mut last_frame = Instant::now();
 
-    while let Some(event) = window.next() {
+    'game: while let Some(event) = window.next() {
         match event {
             Event::Loop(loop_) => match loop_ {
                 Loop::Render(_) => {
+                    let now = Instant::now();
+                    for _ in 0..clock.advance(now - last_frame) {
+                        handle_input(&mut engine, &pressed_keys);
+                        if let State::TopOut = engine.tick() {
+                            break 'game;
+                        }
+                    }
+                    last_frame = now;
+
                     window.draw_2d(&event, |_context, graphics| {
-                        engine.render(graphics);
+                        engine.render_with_options(graphics, &options);
                     });
                 }
-                Loop::Update(_) => {
-                    handle_input(&mut engine, &pressed_keys);
-                    if let State::TopOut = engine.tick() {
-                        break;
-                    }
-                }
+                // Updates are driven by the clock when rendering.
+                Loop::Update(_) => {}
                 _ => window.event(&event),
             },
             Event::Input(Input::Button(button_args)) => {
+                // Toggle the ghost piece on the initial press only.
+                if button_args.button == Button::Keyboard(Key::G)
+                    && button_args.state == ButtonState::Press
+                    && !pressed_keys.contains(&Key::G)
+                {
+                    options.toggle_ghost();
+                }
                 update_held_keys(&mut pressed_keys, button_args);
             }
             _ => window.event(&event),
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+/// The default maximum number of update steps performed on a single frame.
+const MAX_STEPS: u32 = 5;
+
+/// Accumulates elapsed frame time and converts it into a number of fixed-length update steps, so
+/// that the game can be updated at a fixed rate independently of the rendering rate.
+pub struct GameClock {
+    step: Duration,
+    accumulated: Duration,
+    max_steps: u32,
+}
+
+impl GameClock {
+    /// Creates a new clock which performs the specified number of updates per second.
+    pub fn new(updates_per_second: u32) -> GameClock {
+        assert!(
+            updates_per_second > 0,
+            "updates per second must be positive"
+        );
+        GameClock {
+            step: Duration::from_nanos(1_000_000_000 / u64::from(updates_per_second)),
+            accumulated: Duration::from_secs(0),
+            max_steps: MAX_STEPS,
+        }
+    }
+
+    /// Sets the maximum number of update steps performed on a single frame. After a long frame,
+    /// e.g. if the window was dragged or the process was paused, time beyond this many steps is
+    /// discarded rather than caught up on.
+    pub fn set_max_steps(&mut self, max_steps: u32) {
+        self.max_steps = max_steps;
+    }
+
+    /// Adds the time elapsed since the previous frame and returns the number of update steps to
+    /// perform on this frame, up to the maximum number of steps. Time which does not make up a full
+    /// step is carried over to the next frame.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.accumulated += elapsed;
+        let steps = self.accumulated.as_nanos() / self.step.as_nanos();
+        let remainder = self.accumulated.as_nanos() % self.step.as_nanos();
+        self.accumulated = Duration::from_nanos(remainder as u64);
+        std::cmp::min(steps, u128::from(self.max_steps)) as u32
+    }
+
+    /// Returns how far the clock is between the previous and next update step, from 0 (inclusive)
+    /// to 1 (exclusive). This can be used to interpolate between updates when rendering.
+    pub fn alpha(&self) -> f64 {
+        self.accumulated.as_nanos() as f64 / self.step.as_nanos() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_game_clock_advance() {
+        let mut clock = GameClock::new(100);
+        assert_eq!(clock.advance(Duration::from_millis(0)), 0);
+
+        // Partial steps carry over to the next frame.
+        assert_eq!(clock.advance(Duration::from_millis(5)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(5)), 1);
+
+        // Exactly one step.
+        assert_eq!(clock.advance(Duration::from_millis(10)), 1);
+
+        // Long frames perform multiple steps.
+        assert_eq!(clock.advance(Duration::from_millis(25)), 2);
+        assert_eq!(clock.advance(Duration::from_millis(5)), 1);
+
+        // Short frames, i.e. rendering faster than updating.
+        let steps: u32 = (0..12)
+            .map(|_| clock.advance(Duration::from_millis(4)))
+            .sum();
+        assert_eq!(steps, 4);
+    }
+
+    #[test]
+    fn test_game_clock_alpha() {
+        let mut clock = GameClock::new(100);
+        assert_eq!(clock.alpha(), 0.0);
+
+        clock.advance(Duration::from_millis(5));
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+
+        clock.advance(Duration::from_millis(7));
+        assert!((clock.alpha() - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_game_clock_max_steps() {
+        let mut clock = GameClock::new(100);
+        assert_eq!(clock.advance(Duration::from_secs(10)), MAX_STEPS);
+        // Time beyond the maximum is discarded rather than carried over.
+        assert_eq!(clock.advance(Duration::from_millis(0)), 0);
+
+        clock.set_max_steps(3);
+        assert_eq!(clock.advance(Duration::from_millis(45)), 3);
+        // Partial steps are still carried over.
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(clock.advance(Duration::from_millis(5)), 1);
+    }
+}
@@ -0,0 +1,464 @@
+use crate::engine::{
+    base::Engine,
+    core::{Piece, Playfield, Space, Tetromino},
+};
+
+pub const SPACE_SIZE: u32 = 20;
+// Playfield is 10 x 20.
+// Include room for 1 space border on all sides,
+// plus 5 spaces to draw hold and next pieces.
+pub const WIDTH: u32 = 17 * SPACE_SIZE;
+pub const HEIGHT: u32 = 22 * SPACE_SIZE;
+
+/// An RGBA color with components between 0 and 1.
+pub type Color = [f32; 4];
+
+pub const BACKGROUND_COLOR: Color = [0.5, 0.5, 0.5, 1.];
+pub const GREY: Color = [0.1, 0.1, 0.1, 1.];
+pub const GREEN: Color = [0., 1., 0., 1.];
+pub const RED: Color = [1., 0., 0., 1.];
+pub const CYAN: Color = [0., 1., 1., 1.];
+pub const BLUE: Color = [0., 0., 1., 1.];
+pub const DARK_GREY: Color = [0.3, 0.3, 0.3, 1.];
+pub const DARK_CYAN: Color = [0., 0.5, 0.5, 1.];
+
+/// A filled rectangle. Coordinates are in pixels at a scale of 1, relative to the bottom-left corner
+/// of the window.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub color: Color,
+}
+
+/// The direction in which next pieces are stacked, starting from the first next piece.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NextLayout {
+    /// Stack downward, with the first next piece at the top.
+    VerticalDown,
+    /// Stack upward, with the first next piece at the bottom.
+    VerticalUp,
+    /// Stack rightward, with the first next piece on the left. Pieces beyond the first extend past
+    /// the right edge of a window of the default size.
+    Horizontal,
+}
+
+/// Options which control how the game is drawn.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RenderOptions {
+    /// Whether to draw grid lines around each playfield cell.
+    pub grid: bool,
+    pub grid_color: Color,
+    /// The width of grid lines, in pixels.
+    pub grid_width: f64,
+    /// Whether to draw a bar beside the playfield showing the amount of incoming garbage.
+    pub garbage_bar: bool,
+    /// The number of pixels drawn for each pixel of the layout, e.g. 2 for high-DPI displays.
+    pub scale: u32,
+    /// The maximum number of next pieces to draw. The engine may know about more pieces than are
+    /// drawn.
+    pub preview_shown: usize,
+    /// Whether to draw the ghost piece, showing where the current piece would land.
+    pub ghost_enabled: bool,
+    /// How the next pieces are arranged.
+    pub next_layout: NextLayout,
+}
+
+impl RenderOptions {
+    /// Returns the (width, height) of the window, in pixels, at this scale.
+    pub fn window_size(&self) -> (u32, u32) {
+        (WIDTH * self.scale, HEIGHT * self.scale)
+    }
+
+    /// Turns the ghost piece on if it is off, or off if it is on.
+    pub fn toggle_ghost(&mut self) {
+        self.ghost_enabled = !self.ghost_enabled;
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            grid: false,
+            grid_color: DARK_GREY,
+            grid_width: 1.,
+            garbage_bar: false,
+            scale: 1,
+            preview_shown: 5,
+            ghost_enabled: true,
+            next_layout: NextLayout::VerticalDown,
+        }
+    }
+}
+
+/// Converts a rectangle to `[x, y, width, height]` in device coordinates, which range from -1 to 1
+/// across a window of the specified size in pixels. The rectangle is first scaled by the specified
+/// scale.
+pub fn to_device_coordinates(rect: Rect, scale: u32, window_size: (u32, u32)) -> [f64; 4] {
+    let (window_width, window_height) = window_size;
+    let width_scale = 2.0 * f64::from(scale) / f64::from(window_width);
+    let height_scale = 2.0 * f64::from(scale) / f64::from(window_height);
+
+    [
+        -1.0 + f64::from(rect.x) * width_scale,
+        -1.0 + f64::from(rect.y) * height_scale,
+        f64::from(rect.width) * width_scale,
+        f64::from(rect.height) * height_scale,
+    ]
+}
+
+fn block(row: u32, col: u32, color: Color) -> Rect {
+    Rect {
+        x: col * SPACE_SIZE,
+        y: row * SPACE_SIZE,
+        width: SPACE_SIZE,
+        height: SPACE_SIZE,
+        color,
+    }
+}
+
+/// Returns the rectangles whose borders outline the specified (row, col) cells, colored with the
+/// grid color. Returns nothing if grid lines are disabled.
+pub fn cell_borders(
+    cells: impl IntoIterator<Item = (u32, u32)>,
+    options: &RenderOptions,
+) -> Vec<Rect> {
+    if !options.grid {
+        return vec![];
+    }
+    cells
+        .into_iter()
+        .map(|(row, col)| block(row, col, options.grid_color))
+        .collect()
+}
+
+/// Returns the rectangles whose borders outline every visible playfield cell.
+pub fn grid(options: &RenderOptions) -> Vec<Rect> {
+    let cells = (1..=u32::from(Playfield::VISIBLE_HEIGHT))
+        .flat_map(|row| (1..=u32::from(Playfield::WIDTH)).map(move |col| (row, col)));
+    cell_borders(cells, options)
+}
+
+/// Returns the bar showing the specified number of rows of incoming garbage, drawn in the border
+/// left of the playfield. Each row of garbage is one space high, up to the height of the playfield.
+/// Returns `Option::None` if the bar is disabled or there is no garbage.
+pub fn garbage_bar(pending_garbage: u8, options: &RenderOptions) -> Option<Rect> {
+    if !options.garbage_bar || pending_garbage == 0 {
+        return Option::None;
+    }
+    let rows = u32::from(pending_garbage.min(Playfield::VISIBLE_HEIGHT));
+    Option::Some(Rect {
+        x: SPACE_SIZE / 2,
+        y: SPACE_SIZE,
+        width: SPACE_SIZE / 2,
+        height: rows * SPACE_SIZE,
+        color: RED,
+    })
+}
+
+/// Returns the next pieces which should be drawn, i.e. at most the first `preview_shown` pieces.
+pub fn shown_previews<'a>(
+    next_pieces: &'a [Tetromino],
+    options: &RenderOptions,
+) -> &'a [Tetromino] {
+    &next_pieces[..next_pieces.len().min(options.preview_shown)]
+}
+
+/// Returns the (row, col) offset of the bounding box of the next piece at the specified index.
+pub fn next_piece_offset(index: usize, layout: NextLayout) -> (i8, i8) {
+    let i = index as i8;
+    match layout {
+        NextLayout::VerticalDown => (14 - 3 * i, 12),
+        NextLayout::VerticalUp => (2 + 3 * i, 12),
+        NextLayout::Horizontal => (14, 12 + 4 * i),
+    }
+}
+
+/// Returns the rectangles to draw for the current state of the engine with default options, in
+/// drawing order. The background is not included.
+pub fn layout(engine: &impl Engine) -> Vec<Rect> {
+    layout_with_options(engine, &RenderOptions::default())
+}
+
+/// Returns the rectangles to draw for the current state of the engine, in drawing order. The
+/// background is not included.
+pub fn layout_with_options(engine: &impl Engine, options: &RenderOptions) -> Vec<Rect> {
+    fn bounding_box(
+        rects: &mut Vec<Rect>,
+        bounding_box: [[Space; 4]; 4],
+        row_offset: i8,
+        col_offset: i8,
+        color: Color,
+    ) {
+        for (bb_row_index, bb_row) in bounding_box.iter().enumerate() {
+            for (bb_col_index, bb_space) in bb_row.iter().enumerate() {
+                if bb_space == &Space::Block {
+                    let col = (col_offset + bb_col_index as i8) as u32;
+                    let row = (row_offset + bb_row_index as i8) as u32;
+                    if row <= 20 {
+                        rects.push(block(row, col, color));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut rects = vec![Rect {
+        x: SPACE_SIZE,
+        y: SPACE_SIZE,
+        width: 10 * SPACE_SIZE,
+        height: 20 * SPACE_SIZE,
+        color: GREY,
+    }];
+
+    let playfield = engine.get_playfield();
+    // Draw playfield.
+    for row in 1..=Playfield::VISIBLE_HEIGHT {
+        for col in 1..=Playfield::WIDTH {
+            if playfield.get(row, col) == Space::Block {
+                rects.push(block(u32::from(row), u32::from(col), RED));
+            }
+        }
+    }
+
+    // Draw ghost piece below the current piece, so that the current piece is drawn over it.
+    let (current_piece, ghost_piece) = engine.get_active_pieces();
+    if options.ghost_enabled {
+        for (row, col) in ghost_piece.get_cells() {
+            if (1..=20).contains(&row) {
+                rects.push(block(row as u32, col as u32, DARK_CYAN));
+            }
+        }
+    }
+
+    // Draw current piece.
+    for (row, col) in current_piece.get_cells() {
+        if (1..=20).contains(&row) {
+            rects.push(block(row as u32, col as u32, CYAN));
+        }
+    }
+
+    // Draw hold piece at upper right corner.
+    if let Option::Some(hold_piece) = engine.get_hold_piece() {
+        let bb = Piece::new(hold_piece).get_bounding_box();
+        bounding_box(&mut rects, bb, 17, 12, GREEN);
+    }
+
+    // Draw next pieces to right of playfield.
+    let next_pieces = engine.get_next_pieces();
+    for (i, next_piece) in shown_previews(&next_pieces, options).iter().enumerate() {
+        let bb = Piece::new(*next_piece).get_bounding_box();
+        let (row_offset, col_offset) = next_piece_offset(i, options.next_layout);
+        bounding_box(&mut rects, bb, row_offset, col_offset, BLUE);
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_borders() {
+        let options = RenderOptions {
+            grid: true,
+            grid_color: RED,
+            grid_width: 2.,
+            garbage_bar: false,
+            scale: 1,
+            preview_shown: 5,
+            ghost_enabled: true,
+            next_layout: NextLayout::VerticalDown,
+        };
+        let borders = cell_borders(vec![(1, 1), (20, 10)], &options);
+        assert_eq!(
+            borders,
+            vec![
+                Rect {
+                    x: SPACE_SIZE,
+                    y: SPACE_SIZE,
+                    width: SPACE_SIZE,
+                    height: SPACE_SIZE,
+                    color: RED,
+                },
+                Rect {
+                    x: 10 * SPACE_SIZE,
+                    y: 20 * SPACE_SIZE,
+                    width: SPACE_SIZE,
+                    height: SPACE_SIZE,
+                    color: RED,
+                },
+            ]
+        );
+
+        let options = RenderOptions {
+            grid: false,
+            ..options
+        };
+        assert!(cell_borders(vec![(1, 1)], &options).is_empty());
+    }
+
+    #[test]
+    fn test_to_device_coordinates() {
+        fn assert_close(actual: [f64; 4], expected: [f64; 4]) {
+            for (a, e) in actual.iter().zip(expected.iter()) {
+                assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+            }
+        }
+
+        let rect = Rect {
+            x: 170,
+            y: 0,
+            width: 17,
+            height: 44,
+            color: RED,
+        };
+        let expected = [0., -1., 0.1, 0.2];
+
+        // The window is scaled along with the layout, so device coordinates are the same.
+        let options = RenderOptions::default();
+        assert_eq!(options.window_size(), (WIDTH, HEIGHT));
+        assert_close(
+            to_device_coordinates(rect, 1, options.window_size()),
+            expected,
+        );
+
+        let options = RenderOptions {
+            scale: 2,
+            ..RenderOptions::default()
+        };
+        assert_eq!(options.window_size(), (2 * WIDTH, 2 * HEIGHT));
+        assert_close(
+            to_device_coordinates(rect, 2, options.window_size()),
+            expected,
+        );
+
+        // At scale 2 in a window sized for scale 1, the layout fills twice the space.
+        assert_close(
+            to_device_coordinates(rect, 2, (WIDTH, HEIGHT)),
+            [1., -1., 0.2, 0.4],
+        );
+    }
+
+    #[test]
+    fn test_garbage_bar() {
+        let options = RenderOptions {
+            garbage_bar: true,
+            ..RenderOptions::default()
+        };
+        assert_eq!(garbage_bar(0, &options), Option::None);
+        assert_eq!(garbage_bar(5, &RenderOptions::default()), Option::None);
+
+        let bar = garbage_bar(5, &options).unwrap();
+        assert_eq!((bar.y, bar.height), (SPACE_SIZE, 5 * SPACE_SIZE));
+        assert_eq!(bar.color, RED);
+        // The bar is in the left border.
+        assert!(bar.x + bar.width <= SPACE_SIZE);
+
+        // The bar never extends above the playfield.
+        let bar = garbage_bar(30, &options).unwrap();
+        assert_eq!(bar.height, 20 * SPACE_SIZE);
+    }
+
+    #[test]
+    fn test_grid() {
+        assert!(grid(&RenderOptions::default()).is_empty());
+
+        let options = RenderOptions {
+            grid: true,
+            ..RenderOptions::default()
+        };
+        let borders = grid(&options);
+        assert_eq!(borders.len(), 200);
+        // Every cell is inside the playfield.
+        for rect in borders {
+            assert!((SPACE_SIZE..=10 * SPACE_SIZE).contains(&rect.x));
+            assert!((SPACE_SIZE..=20 * SPACE_SIZE).contains(&rect.y));
+            assert_eq!(rect.color, options.grid_color);
+        }
+    }
+
+    #[test]
+    fn test_next_piece_offset() {
+        let offsets = |layout| {
+            (0..5)
+                .map(|i| next_piece_offset(i, layout))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            offsets(NextLayout::VerticalDown),
+            vec![(14, 12), (11, 12), (8, 12), (5, 12), (2, 12)]
+        );
+        assert_eq!(
+            offsets(NextLayout::VerticalUp),
+            vec![(2, 12), (5, 12), (8, 12), (11, 12), (14, 12)]
+        );
+        assert_eq!(
+            offsets(NextLayout::Horizontal),
+            vec![(14, 12), (14, 16), (14, 20), (14, 24), (14, 28)]
+        );
+    }
+
+    #[test]
+    fn test_shown_previews() {
+        use Tetromino::*;
+        let next_pieces = [I, O, T, S, Z];
+
+        let options = RenderOptions {
+            preview_shown: 3,
+            ..RenderOptions::default()
+        };
+        assert_eq!(shown_previews(&next_pieces, &options), &[I, O, T]);
+
+        // All pieces are shown by default.
+        assert_eq!(
+            shown_previews(&next_pieces, &RenderOptions::default()),
+            &next_pieces
+        );
+
+        // Showing more pieces than are known shows every known piece.
+        let options = RenderOptions {
+            preview_shown: 7,
+            ..RenderOptions::default()
+        };
+        assert_eq!(shown_previews(&next_pieces, &options), &next_pieces);
+
+        let options = RenderOptions {
+            preview_shown: 0,
+            ..RenderOptions::default()
+        };
+        assert!(shown_previews(&next_pieces, &options).is_empty());
+    }
+
+    #[test]
+    fn test_toggle_ghost() {
+        let mut options = RenderOptions::default();
+        assert!(options.ghost_enabled);
+        options.toggle_ghost();
+        assert!(!options.ghost_enabled);
+        options.toggle_ghost();
+        assert!(options.ghost_enabled);
+    }
+
+    #[test]
+    fn test_layout_ghost() {
+        use crate::engine::base::BaseEngine;
+
+        // The current piece is above the visible playfield, but its ghost is at the bottom.
+        let engine = BaseEngine::new();
+        let ghost_blocks = |options: &RenderOptions| {
+            layout_with_options(&engine, options)
+                .iter()
+                .filter(|rect| rect.color == DARK_CYAN)
+                .count()
+        };
+        assert_eq!(ghost_blocks(&RenderOptions::default()), 4);
+
+        let mut options = RenderOptions::default();
+        options.toggle_ghost();
+        assert_eq!(ghost_blocks(&options), 0);
+    }
+}
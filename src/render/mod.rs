@@ -0,0 +1,79 @@
+pub mod clock;
+mod layout;
+pub mod offscreen;
+pub mod text;
+
+use graphics::{draw_state::DrawState, rectangle::Rectangle, Graphics};
+use piston::{event_loop::EventLoop, window::WindowSettings};
+use piston_window::PistonWindow;
+
+pub use self::layout::{NextLayout, RenderOptions};
+
+use self::layout::BACKGROUND_COLOR;
+use crate::engine::{base::Engine, single::SinglePlayerEngine};
+
+const DEFAULT_DRAW_STATE: DrawState = DrawState {
+    scissor: Option::None,
+    stencil: Option::None,
+    blend: Option::None,
+};
+const IDENTITY_TRANSFORMATION_MATRIX: [[f64; 3]; 2] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+pub trait PistonRender {
+    fn create_window_with_options(&self, options: &RenderOptions) -> Box<PistonWindow>;
+    fn render_with_options<G: Graphics>(&self, graphics: &mut G, options: &RenderOptions);
+
+    fn create_window(&self) -> Box<PistonWindow> {
+        self.create_window_with_options(&RenderOptions::default())
+    }
+
+    fn render<G: Graphics>(&self, graphics: &mut G) {
+        self.render_with_options(graphics, &RenderOptions::default());
+    }
+}
+
+impl PistonRender for SinglePlayerEngine {
+    fn create_window_with_options(&self, options: &RenderOptions) -> Box<PistonWindow> {
+        let mut window: PistonWindow = WindowSettings::new("tet-rs", options.window_size())
+            .exit_on_esc(true)
+            .resizable(false)
+            .build()
+            .unwrap();
+        window.set_max_fps(120);
+
+        Box::new(window)
+    }
+
+    fn render_with_options<G: Graphics>(&self, graphics: &mut G, options: &RenderOptions) {
+        let window_size = options.window_size();
+        let convert_coordinates =
+            |rect| layout::to_device_coordinates(rect, options.scale, window_size);
+
+        graphics.clear_color(BACKGROUND_COLOR);
+
+        let garbage_bar = layout::garbage_bar(self.pending_garbage(), options);
+        for rect in layout::layout_with_options(self, options)
+            .into_iter()
+            .chain(garbage_bar)
+        {
+            Rectangle::new(rect.color).draw(
+                convert_coordinates(rect),
+                &DEFAULT_DRAW_STATE,
+                IDENTITY_TRANSFORMATION_MATRIX,
+                graphics,
+            );
+        }
+
+        // Borders are drawn centered on the edges of each cell, so adjacent cells share lines.
+        // Convert the width from pixels, then halve it to get the radius.
+        let border_radius = options.grid_width / f64::from(window_size.0);
+        for rect in layout::grid(options) {
+            Rectangle::new_border(rect.color, border_radius).draw(
+                convert_coordinates(rect),
+                &DEFAULT_DRAW_STATE,
+                IDENTITY_TRANSFORMATION_MATRIX,
+                graphics,
+            );
+        }
+    }
+}
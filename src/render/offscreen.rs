@@ -0,0 +1,98 @@
+use super::layout::{self, Color, Rect, BACKGROUND_COLOR, HEIGHT, WIDTH};
+use crate::engine::base::Engine;
+
+/// Renders the current state of the engine to a buffer of RGBA bytes without creating a window.
+/// The layout is the same as `PistonRender`, scaled to the specified dimensions. Pixels are
+/// stored row by row, starting at the top-left corner.
+pub fn render_to_rgba(engine: &impl Engine, width: u32, height: u32) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(width as usize * height as usize * 4);
+    for _ in 0..width * height {
+        buffer.extend_from_slice(&to_rgba(BACKGROUND_COLOR));
+    }
+
+    for rect in layout::layout(engine) {
+        fill_rect(&mut buffer, width, height, rect);
+    }
+
+    buffer
+}
+
+/// Fills the specified rectangle, scaling it from layout coordinates to the buffer dimensions.
+fn fill_rect(buffer: &mut [u8], width: u32, height: u32, rect: Rect) {
+    fn scale(value: u32, to: u32, from: u32) -> u32 {
+        (u64::from(value) * u64::from(to) / u64::from(from)) as u32
+    }
+
+    let x_start = scale(rect.x, width, WIDTH).min(width);
+    let x_end = scale(rect.x + rect.width, width, WIDTH).min(width);
+    // Layout coordinates start at the bottom, but the buffer starts at the top.
+    let y_start = height.saturating_sub(scale(rect.y + rect.height, height, HEIGHT));
+    let y_end = height.saturating_sub(scale(rect.y, height, HEIGHT));
+
+    let rgba = to_rgba(rect.color);
+    for y in y_start..y_end {
+        for x in x_start..x_end {
+            let index = (y as usize * width as usize + x as usize) * 4;
+            buffer[index..index + 4].copy_from_slice(&rgba);
+        }
+    }
+}
+
+fn to_rgba(color: Color) -> [u8; 4] {
+    let mut rgba = [0; 4];
+    for (byte, component) in rgba.iter_mut().zip(color.iter()) {
+        *byte = (component * 255.).round() as u8;
+    }
+    rgba
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::base::BaseEngine;
+    use crate::render::layout::{CYAN, GREY, SPACE_SIZE};
+
+    fn pixel(buffer: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let index = (y as usize * width as usize + x as usize) * 4;
+        [
+            buffer[index],
+            buffer[index + 1],
+            buffer[index + 2],
+            buffer[index + 3],
+        ]
+    }
+
+    #[test]
+    fn test_render_to_rgba() {
+        let mut engine = BaseEngine::new();
+        // Every tetromino in spawn rotation has a block in the second column of the third row of
+        // its bounding box. Drop the piece until that block is in the top row of the playfield.
+        while engine.get_current_piece().get_row() > 18 {
+            engine.tick();
+        }
+
+        let buffer = render_to_rgba(&engine, WIDTH, HEIGHT);
+        assert_eq!(buffer.len(), (WIDTH * HEIGHT * 4) as usize);
+
+        // Row 20, column 5 is one space from the top of the window and five spaces from the left.
+        let (x, y) = (5 * SPACE_SIZE + SPACE_SIZE / 2, SPACE_SIZE + SPACE_SIZE / 2);
+        assert_eq!(pixel(&buffer, WIDTH, x, y), to_rgba(CYAN));
+        // Row 1, column 1 is empty.
+        let (x, y) = (
+            SPACE_SIZE + SPACE_SIZE / 2,
+            HEIGHT - SPACE_SIZE - SPACE_SIZE / 2,
+        );
+        assert_eq!(pixel(&buffer, WIDTH, x, y), to_rgba(GREY));
+        // The border is the background color.
+        assert_eq!(pixel(&buffer, WIDTH, 0, 0), to_rgba(BACKGROUND_COLOR));
+
+        // The same pixels at half scale.
+        let buffer = render_to_rgba(&engine, WIDTH / 2, HEIGHT / 2);
+        assert_eq!(buffer.len(), (WIDTH / 2 * HEIGHT / 2 * 4) as usize);
+        let (x, y) = (
+            5 * SPACE_SIZE / 2 + SPACE_SIZE / 4,
+            SPACE_SIZE / 2 + SPACE_SIZE / 4,
+        );
+        assert_eq!(pixel(&buffer, WIDTH / 2, x, y), to_rgba(CYAN));
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt::Write;
+
+use crate::engine::{
+    base::Engine,
+    core::{Playfield, Space},
+};
+
+/// Renders the visible playfield, including the current piece, as plain text. Each row is written
+/// on its own line, starting at the top. Blocks are drawn as `#` and empty spaces as `.`.
+pub fn render(engine: &impl Engine) -> String {
+    render_with(engine, |_, _, _| "#".to_string())
+}
+
+/// Renders the visible playfield in the same format as `render`, but colors each block by its
+/// tetromino using 24-bit ANSI escape sequences. Blocks which were not placed by a tetromino are
+/// not colored.
+pub fn render_ansi(engine: &impl Engine) -> String {
+    render_with(engine, |playfield, row, col| {
+        match playfield.get_tetromino(row, col) {
+            Option::Some(shape) => {
+                let [r, g, b] = shape.color();
+                format!("\x1b[38;2;{};{};{}m#\x1b[0m", r, g, b)
+            }
+            Option::None => "#".to_string(),
+        }
+    })
+}
+
+fn render_with(engine: &impl Engine, block: impl Fn(&Playfield, u8, u8) -> String) -> String {
+    let playfield = engine
+        .get_playfield()
+        .with_piece(engine.get_current_piece());
+
+    let mut text = String::new();
+    for row in (1..=Playfield::VISIBLE_HEIGHT).rev() {
+        for col in 1..=Playfield::WIDTH {
+            match playfield.get(row, col) {
+                Space::Empty => text.push('.'),
+                Space::Block => text.push_str(&block(&playfield, row, col)),
+            }
+        }
+        writeln!(text).unwrap();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::base::BaseEngine;
+    use crate::engine::core::Tetromino;
+
+    #[test]
+    fn test_render() {
+        let mut engine = BaseEngine::new();
+        engine.input_hard_drop();
+        engine.tick();
+
+        let text = render(&engine);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), Playfield::VISIBLE_HEIGHT as usize);
+
+        // Every row is drawn from left to right, starting at the top.
+        let playfield = engine
+            .get_playfield()
+            .with_piece(engine.get_current_piece());
+        for (i, line) in lines.iter().enumerate() {
+            let row = Playfield::VISIBLE_HEIGHT - i as u8;
+            let expected: String = (1..=Playfield::WIDTH)
+                .map(|col| match playfield.get(row, col) {
+                    Space::Empty => '.',
+                    Space::Block => '#',
+                })
+                .collect();
+            assert_eq!(line, &expected);
+        }
+    }
+
+    #[test]
+    fn test_render_ansi() {
+        let mut engine = BaseEngine::new();
+        // Hard drop pieces until an I piece has been locked.
+        loop {
+            let shape = *engine.get_current_piece().get_piece().get_shape();
+            engine.input_hard_drop();
+            engine.tick();
+            engine.tick();
+            if shape == Tetromino::I {
+                break;
+            }
+        }
+
+        let text = render_ansi(&engine);
+        assert!(text.contains("\x1b[38;2;0;255;255m#\x1b[0m"));
+
+        // Without escape sequences, the output is the same as the plain rendering.
+        let mut plain = String::new();
+        let mut in_escape = false;
+        for c in text.chars() {
+            match c {
+                '\x1b' => in_escape = true,
+                'm' if in_escape => in_escape = false,
+                _ if in_escape => (),
+                _ => plain.push(c),
+            }
+        }
+        assert_eq!(plain, render(&engine));
+    }
+}